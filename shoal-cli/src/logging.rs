@@ -0,0 +1,57 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Installs the tracing subscriber, logging to stderr.
+pub fn init(verbose: u8, quiet: bool, format: LogFormat) {
+    let filter = filter(verbose, quiet);
+
+    let (text, json) = match format {
+        LogFormat::Text => (
+            Some(
+                fmt::layer()
+                    .with_writer(std::io::stderr)
+                    .with_ansi(std::io::stderr().is_terminal()),
+            ),
+            None,
+        ),
+        LogFormat::Json => (None, Some(fmt::layer().json().with_writer(std::io::stderr))),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .init();
+}
+
+fn filter(verbose: u8, quiet: bool) -> EnvFilter {
+    let level = match (quiet, verbose) {
+        (true, _) => Some("error"),
+        (false, 0) => None,
+        (false, 1) => Some("debug"),
+        (false, _) => Some("trace"),
+    };
+    if let Some(level) = level {
+        return EnvFilter::new(format!("warn,shoal={level},shoal_core={level}"));
+    }
+
+    for variable in ["SHOAL_LOG", "RUST_LOG"] {
+        if let Ok(directives) = std::env::var(variable) {
+            match EnvFilter::try_new(&directives) {
+                Ok(filter) => return filter,
+                Err(error) => eprintln!("Ignoring invalid {variable} '{directives}': {error}"),
+            }
+        }
+    }
+
+    EnvFilter::new("warn,shoal=info,shoal_core=info")
+}
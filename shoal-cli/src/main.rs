@@ -1,7 +1,27 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use shoal_core::{self, create_shoal_manager};
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use crate::logging::LogFormat;
+use crate::output::{DisplayFormat, colour_enabled, log_prefix, print_json, print_table};
+use anyhow::anyhow;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use shoal_core::{
+    self, GraphFormat, OutputFormat, Result, ShoalError, ShoalManager,
+    traits::OutputStream,
+    types::{
+        config_source::{ConfigEntry, ConfigSource},
+        logs::{LogLine, LogOptions},
+        provenance::{Definition, DefinitionKind},
+        stack_status::StackStatus,
+        validation::Severity,
+    },
+};
+
+mod logging;
+mod output;
 
 #[derive(Parser, Debug)]
 #[command(name = "shoal")]
@@ -9,25 +29,519 @@ use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberI
 struct Args {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Log more detail; -v for debug, -vv for trace (including environment values)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Format of the logs written to stderr
+    #[arg(long, value_enum, global = true, default_value_t)]
+    pub log_format: LogFormat,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    Up { stack_name: String },
-    Down { stack_name: String },
+    Up {
+        stack_name: String,
+        #[command(flatten)]
+        layers: LayerArgs,
+        /// Write the compose file to this path instead of starting the stack
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Format of the written compose file; inferred from the output extension if omitted
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    Down {
+        stack_name: String,
+    },
+    /// Render the compose file for a stack without running docker
+    Render {
+        stack_name: String,
+        #[command(flatten)]
+        layers: LayerArgs,
+        /// Write the compose file to this path instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Show the logs of a running stack, or of some of its services
+    Logs {
+        stack_name: String,
+        services: Vec<String>,
+        /// Keep streaming new log lines
+        #[arg(short, long)]
+        follow: bool,
+        /// Only show logs since a timestamp or relative time, e.g. 10m
+        #[arg(long)]
+        since: Option<String>,
+        /// Number of lines to show from the end of each service's logs
+        #[arg(long)]
+        tail: Option<String>,
+    },
+    /// Restart a service of a running stack
+    Restart {
+        stack_name: String,
+        service: String,
+    },
+    /// Rebuild a locally built service and recreate its container
+    Rebuild {
+        stack_name: String,
+        service: String,
+        /// Also recreate the services that depend on it
+        #[arg(long)]
+        with_dependents: bool,
+    },
+    /// Run a command in a service of a running stack
+    Exec {
+        stack_name: String,
+        service: String,
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Open a shell in a service of a running stack, trying bash, then sh, then ash
+    Shell {
+        stack_name: String,
+        service: String,
+    },
+    /// Show the container state of each service in a stack, or of every stack
+    Status {
+        stack_name: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: DisplayFormat,
+    },
+    /// Print the resolved service graph of a stack as Graphviz DOT or Mermaid
+    Graph {
+        stack_name: String,
+        #[command(flatten)]
+        layers: LayerArgs,
+        #[arg(long, value_enum, default_value_t)]
+        format: GraphStyle,
+    },
+    /// Check every stack, service and override without starting anything
+    Validate {
+        #[arg(long, value_enum, default_value_t)]
+        format: DisplayFormat,
+    },
+    /// Show which files define a stack, service or override, and set each stack's fields
+    Which {
+        name: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: DisplayFormat,
+    },
+    /// List the stacks, services or overrides Shoal has loaded
+    List {
+        #[arg(value_enum)]
+        kind: ListKind,
+        #[arg(long, value_enum, default_value_t)]
+        format: DisplayFormat,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct LayerArgs {
+    /// Apply a named override; repeat to layer several, later ones winning
+    #[arg(long = "override", value_name = "NAME")]
+    overrides: Vec<String>,
+    /// Build a service from a local checkout instead of its source; repeatable
+    #[arg(long, value_name = "SERVICE=PATH", value_parser = parse_local_source)]
+    local: Vec<(String, PathBuf)>,
+}
+
+impl LayerArgs {
+    fn shoal_manager(&self) -> Result<ShoalManager> {
+        self.local
+            .iter()
+            .fold(ShoalManager::builder(), |builder, (service, path)| {
+                builder.local_source(service.clone(), path.clone())
+            })
+            .build()
+    }
+
+    fn stack_name(&self, stack_name: String) -> String {
+        std::iter::once(stack_name)
+            .chain(self.overrides.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ListKind {
+    Stacks,
+    Services,
+    Overrides,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum GraphStyle {
+    #[default]
+    Dot,
+    Mermaid,
+}
+
+impl From<GraphStyle> for GraphFormat {
+    fn from(style: GraphStyle) -> Self {
+        match style {
+            GraphStyle::Dot => GraphFormat::Dot,
+            GraphStyle::Mermaid => GraphFormat::Mermaid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Yaml,
+    Json,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Yaml => OutputFormat::Yaml,
+            Format::Json => OutputFormat::Json,
+        }
+    }
+}
+
+fn parse_local_source(value: &str) -> std::result::Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((service, path)) if !service.is_empty() && !path.is_empty() => {
+            Ok((service.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected SERVICE=PATH, got '{value}'")),
+    }
 }
 
-fn main() -> Result<()> {
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::new("debug"))
-        .init();
+fn output_format(format: Option<Format>, output: Option<&Path>) -> OutputFormat {
+    match format {
+        Some(format) => format.into(),
+        None if output.is_some_and(|p| p.extension().is_some_and(|e| e == "json")) => {
+            OutputFormat::Json
+        }
+        None => OutputFormat::Yaml,
+    }
+}
+
+fn source_cells(source: Option<&ConfigSource>) -> [String; 2] {
+    match source {
+        Some(source) => [source.scope.to_string(), source.path.display().to_string()],
+        None => ["-".to_string(), "-".to_string()],
+    }
+}
 
+fn entry_rows(entries: &[ConfigEntry]) -> Vec<Vec<String>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let [scope, path] = source_cells(entry.source.as_ref());
+            vec![entry.name.clone(), entry.description.clone(), scope, path]
+        })
+        .collect()
+}
+
+fn list(shoal_manager: &ShoalManager, kind: ListKind, format: DisplayFormat) -> Result<()> {
+    const HEADERS: [&str; 4] = ["NAME", "DESCRIPTION", "SCOPE", "PATH"];
+
+    match (kind, format) {
+        (ListKind::Stacks, DisplayFormat::Json) => print_json(&shoal_manager.list_stacks()),
+        (ListKind::Services, DisplayFormat::Json) => print_json(&shoal_manager.list_services()),
+        (ListKind::Overrides, DisplayFormat::Json) => print_json(&shoal_manager.list_overrides()),
+        (ListKind::Stacks, DisplayFormat::Table) => {
+            print_table(&HEADERS, &entry_rows(&shoal_manager.list_stacks()), 0);
+            Ok(())
+        }
+        (ListKind::Services, DisplayFormat::Table) => {
+            print_table(&HEADERS, &entry_rows(&shoal_manager.list_services()), 0);
+            Ok(())
+        }
+        (ListKind::Overrides, DisplayFormat::Table) => {
+            for (stack, entries) in shoal_manager.list_overrides() {
+                println!("{stack}");
+                print_table(&HEADERS, &entry_rows(&entries), 2);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn which(shoal_manager: &ShoalManager, name: String, format: DisplayFormat) -> Result<()> {
+    let definitions = shoal_manager.which(&name);
+    if definitions.is_empty() {
+        return Err(anyhow!("no stack, service or override named '{name}'").into());
+    }
+
+    // An override for any stack is named without one, and has no fields until applied.
+    let is_stack = definitions.iter().any(|definition| {
+        definition.name == name
+            && match definition.kind {
+                DefinitionKind::Stack => true,
+                DefinitionKind::Override => definition.name.contains('.'),
+                DefinitionKind::Service => false,
+            }
+    });
+
+    if let DisplayFormat::Json = format {
+        let fields = match is_stack {
+            true => Some(shoal_manager.provenance(name.as_str())?),
+            false => None,
+        };
+        return print_json(&json!({ "definitions": definitions, "fields": fields }));
+    }
+
+    for Definition {
+        kind,
+        name,
+        active,
+        shadowed,
+    } in &definitions
+    {
+        println!("{kind} {name}");
+        let rows: Vec<Vec<String>> = std::iter::once(("active", active.as_ref()))
+            .chain(
+                shadowed
+                    .iter()
+                    .rev()
+                    .map(|source| ("shadowed", Some(source))),
+            )
+            .map(|(status, source)| {
+                let [scope, path] = source_cells(source);
+                vec![status.to_string(), scope, path]
+            })
+            .collect();
+        print_table(&["STATUS", "SCOPE", "PATH"], &rows, 2);
+    }
+
+    if is_stack {
+        let provenance = shoal_manager.provenance(name.as_str())?;
+        let rows: Vec<Vec<String>> = provenance
+            .services
+            .into_iter()
+            .flat_map(|(service, fields)| {
+                fields.into_iter().map(move |origin| {
+                    let [_, path] = source_cells(origin.source.as_ref());
+                    vec![
+                        service.clone(),
+                        origin.field.to_string(),
+                        origin.value,
+                        origin.layer.to_string(),
+                        path,
+                    ]
+                })
+            })
+            .collect();
+        println!("fields");
+        print_table(&["SERVICE", "FIELD", "VALUE", "LAYER", "PATH"], &rows, 2);
+    }
+    Ok(())
+}
+
+fn logs(shoal_manager: &ShoalManager, stack_name: String, options: LogOptions) -> Result<()> {
+    let services = shoal_manager.stack_services(&stack_name)?;
+    let width = services.iter().map(String::len).max().unwrap_or(0);
+    let colour = colour_enabled();
+
+    shoal_manager.logs(
+        stack_name,
+        &options,
+        &mut |line: LogLine| match (line.service, line.stream) {
+            (Some(service), _) => {
+                let index = services.iter().position(|s| *s == service).unwrap_or(0);
+                let prefix = log_prefix(&service, index, width, colour);
+                println!("{prefix} {}", line.message);
+            }
+            (None, OutputStream::Stdout) => println!("{}", line.message),
+            (None, OutputStream::Stderr) => eprintln!("{}", line.message),
+        },
+    )
+}
+
+fn status(
+    shoal_manager: &ShoalManager,
+    stack_name: Option<String>,
+    format: DisplayFormat,
+) -> Result<()> {
+    const HEADERS: [&str; 5] = ["SERVICE", "STATE", "HEALTH", "PORTS", "UPTIME"];
+
+    let statuses = match stack_name {
+        Some(stack_name) => vec![shoal_manager.status(stack_name)?],
+        None => shoal_manager.status_all()?,
+    };
+
+    if let DisplayFormat::Json = format {
+        return print_json(&statuses);
+    }
+
+    for StackStatus {
+        stack,
+        state,
+        services,
+    } in statuses
+    {
+        println!("{stack}: {state}");
+        let rows: Vec<Vec<String>> = services
+            .into_iter()
+            .map(|service| {
+                vec![
+                    service.service,
+                    service.state,
+                    service.health.unwrap_or_else(|| "-".to_string()),
+                    service.ports.join(", "),
+                    service.uptime.unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect();
+        print_table(&HEADERS, &rows, 2);
+    }
+    Ok(())
+}
+
+fn validate(format: DisplayFormat) -> Result<()> {
+    let report = ShoalManager::builder().validate()?;
+
+    match format {
+        DisplayFormat::Json => print_json(&report)?,
+        DisplayFormat::Table => {
+            for finding in &report.findings {
+                println!("{finding}");
+            }
+            let errors = report.count(Severity::Error);
+            let warnings = report.count(Severity::Warning);
+            if report.findings.is_empty() {
+                println!("Configuration is valid.");
+            } else {
+                println!("{errors} error(s), {warnings} warning(s)");
+            }
+        }
+    }
+
+    if report.is_valid() {
+        Ok(())
+    } else {
+        Err(ShoalError::InvalidConfig {
+            errors: report.count(Severity::Error),
+        })
+    }
+}
+
+fn exit_code(error: &ShoalError) -> u8 {
+    match error {
+        ShoalError::Other(_) => 1,
+        ShoalError::ConfigParse { .. } => 3,
+        ShoalError::UnknownStack { .. } => 4,
+        ShoalError::UnknownOverride { .. } => 5,
+        ShoalError::MissingService { .. } => 6,
+        ShoalError::DependencyCycle { .. } => 7,
+        ShoalError::StackNotRunning { .. } | ShoalError::CommandFailed { .. } => 8,
+        ShoalError::InvalidConfig { .. } => 9,
+    }
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    let shoal_manager = create_shoal_manager()?;
+    logging::init(args.verbose, args.quiet, args.log_format);
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error:#}");
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let shoal_manager = || ShoalManager::builder().build();
 
     match args.command {
-        Commands::Up { stack_name } => shoal_manager.up(stack_name),
-        Commands::Down { stack_name } => shoal_manager.down(stack_name),
+        Commands::Up {
+            stack_name,
+            layers,
+            output: Some(output),
+            format,
+        } => layers.shoal_manager()?.export(
+            layers.stack_name(stack_name),
+            output_format(format, Some(&output)),
+            &output,
+        ),
+        Commands::Up {
+            stack_name, layers, ..
+        } => layers.shoal_manager()?.up(layers.stack_name(stack_name)),
+        Commands::Down { stack_name } => shoal_manager()?.down(stack_name),
+        Commands::Logs {
+            stack_name,
+            services,
+            follow,
+            since,
+            tail,
+        } => logs(
+            &shoal_manager()?,
+            stack_name,
+            LogOptions {
+                services,
+                follow,
+                since,
+                tail,
+            },
+        ),
+        Commands::Restart {
+            stack_name,
+            service,
+        } => shoal_manager()?.restart(stack_name, &service),
+        Commands::Rebuild {
+            stack_name,
+            service,
+            with_dependents,
+        } => shoal_manager()?.rebuild(stack_name, &service, with_dependents),
+        Commands::Exec {
+            stack_name,
+            service,
+            command,
+        } => shoal_manager()?.exec(stack_name, &service, &command),
+        Commands::Shell {
+            stack_name,
+            service,
+        } => shoal_manager()?.shell(stack_name, &service),
+        Commands::Status { stack_name, format } => status(&shoal_manager()?, stack_name, format),
+        Commands::Graph {
+            stack_name,
+            layers,
+            format,
+        } => {
+            let stack_name = layers.stack_name(stack_name);
+            println!(
+                "{}",
+                layers
+                    .shoal_manager()?
+                    .render_graph(stack_name, format.into())?
+            );
+            Ok(())
+        }
+        Commands::Which { name, format } => which(&shoal_manager()?, name, format),
+        Commands::List { kind, format } => list(&shoal_manager()?, kind, format),
+        // Validation loads the config itself so that broken files are reported, not fatal.
+        Commands::Validate { format } => validate(format),
+        Commands::Render {
+            stack_name,
+            layers,
+            output,
+            format,
+        } => {
+            let stack_name = layers.stack_name(stack_name);
+            let format = output_format(format, output.as_deref());
+            let shoal_manager = layers.shoal_manager()?;
+            match output {
+                Some(output) => shoal_manager.export(stack_name, format, &output),
+                None => {
+                    println!("{}", shoal_manager.render(stack_name, format)?);
+                    Ok(())
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,60 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+const PREFIX_COLOURS: [u8; 6] = [36, 33, 32, 35, 34, 31];
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum DisplayFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> shoal_core::Result<()> {
+    let json = serde_json::to_string_pretty(value).map_err(anyhow::Error::from)?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Prints rows as left-aligned columns sized to their widest cell.
+pub fn print_table(headers: &[&str], rows: &[Vec<String>], indent: usize) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{:indent$}{}", "", line.trim_end())
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    for row in rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+/// Whether stdout should get ANSI colours: only for terminals, and never with `NO_COLOR` set.
+pub fn colour_enabled() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Pads `prefix` to `width` and, when `colour` is set, wraps it in the colour for `index`.
+pub fn log_prefix(prefix: &str, index: usize, width: usize, colour: bool) -> String {
+    let padded = format!("{prefix:<width$}");
+    if colour {
+        let code = PREFIX_COLOURS[index % PREFIX_COLOURS.len()];
+        format!("\x1b[{code}m{padded} |\x1b[0m")
+    } else {
+        format!("{padded} |")
+    }
+}
@@ -6,9 +6,15 @@ use crate::types::{
     docker_network::DockerNetwork, docker_service::DockerComposeFile, docker_service::DockerService,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use tracing::debug;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Yaml,
+    Json,
+}
+
 pub struct ComposeFileManager<FS: FileSystem, PP: PathProvider> {
     file_system: FS,
     path_provider: PP,
@@ -22,12 +28,11 @@ impl<FS: FileSystem, PP: PathProvider> ComposeFileManager<FS, PP> {
         }
     }
 
-    pub fn generate_compose_file(
+    pub fn build_compose_file(
         &self,
         network_name: &str,
         docker_services: HashMap<String, DockerService>,
-        compose_path: &PathBuf,
-    ) -> Result<()> {
+    ) -> DockerComposeFile {
         let mut networks = HashMap::new();
         networks.insert(
             network_name.to_string(),
@@ -35,19 +40,45 @@ impl<FS: FileSystem, PP: PathProvider> ComposeFileManager<FS, PP> {
         );
 
         debug!("Generating docker compose object.");
-        let compose = DockerComposeFile {
+        DockerComposeFile {
             services: docker_services,
             networks,
+        }
+    }
+
+    pub fn render_compose_file(
+        &self,
+        compose: &DockerComposeFile,
+        format: OutputFormat,
+    ) -> Result<String> {
+        let rendered = match format {
+            OutputFormat::Yaml => serde_saphyr::to_string(compose)?,
+            OutputFormat::Json => serde_json::to_string_pretty(compose)?,
         };
+        Ok(rendered)
+    }
 
+    pub fn write_compose_file(
+        &self,
+        compose: &DockerComposeFile,
+        compose_path: &Path,
+        format: OutputFormat,
+    ) -> Result<()> {
         debug!("Compose object generated, saving to file.");
-        let compose_yaml = serde_saphyr::to_string(&compose)?;
-        self.file_system.write_file(compose_path, &compose_yaml)?;
+        let rendered = self.render_compose_file(compose, format)?;
+        self.file_system.write_file(compose_path, &rendered)?;
         debug!("Compose saved to {:?}", compose_path);
 
         Ok(())
     }
 
+    /// Reads back a compose file Shoal generated, untyped.
+    pub fn read_compose_file(&self, compose_path: &Path) -> Result<serde_json::Value> {
+        let contents = self.file_system.read_file(compose_path)?;
+        serde_saphyr::from_str(&contents)
+            .with_context(|| format!("Failed to parse compose file {}", compose_path.display()))
+    }
+
     pub fn ensure_compose_path(&self, stack_name: &str) -> Result<PathBuf> {
         let stack_dir = self.stack_dir(stack_name)?;
         if !self.file_system.exists(&stack_dir) {
@@ -1,32 +1,23 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::de::DeserializeOwned;
 use std::path::Path;
 use std::{collections::HashMap, path::PathBuf};
 use tracing::{debug, warn};
 
+use crate::error::ShoalError;
 use crate::traits::{FileSystem, PathProvider};
-use crate::types::{service::Service, stack::Stack, stack_override::StackOverride};
+use crate::types::{
+    config_source::{ConfigSource, FileScope},
+    service::Service,
+    stack::Stack,
+    stack_override::StackOverride,
+};
 
 pub struct ConfigLoader<FS: FileSystem, PP: PathProvider> {
     file_system: FS,
     path_provider: PP,
 }
 
-#[derive(Clone, Copy, Debug)]
-enum FileScope {
-    Local,
-    Global,
-}
-
-impl std::fmt::Display for FileScope {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            FileScope::Local => write!(f, "local"),
-            FileScope::Global => write!(f, "global"),
-        }
-    }
-}
-
 impl<FS: FileSystem, PP: PathProvider> ConfigLoader<FS, PP> {
     pub fn new(file_system: FS, path_provider: PP) -> Self {
         Self {
@@ -36,37 +27,17 @@ impl<FS: FileSystem, PP: PathProvider> ConfigLoader<FS, PP> {
     }
 
     pub fn load_overrides(&self) -> Result<HashMap<String, StackOverride>> {
-        let local_path = self.path_provider.current_dir()?.join("overrides");
-        let global_path = self.path_provider.home_dir()?.join(".shoal/overrides");
-
-        let search_paths: [(FileScope, PathBuf); 2] = [
-            (FileScope::Global, global_path),
-            (FileScope::Local, local_path),
-        ];
-
         self.load_items(
-            &search_paths,
-            "Overrides",
+            self.override_files()?,
             "overrides",
             "Stack override detected.",
-            |stack_override: &StackOverride| {
-                format!("{}-{}", &stack_override.stack, &stack_override.name)
-            },
+            |stack_override: &StackOverride| stack_override.key(),
         )
     }
 
     pub fn load_stacks(&self) -> Result<HashMap<String, Stack>> {
-        let local_path = self.path_provider.current_dir()?.join("stacks");
-        let global_path = self.path_provider.home_dir()?.join(".shoal/stacks");
-
-        let search_paths: [(FileScope, PathBuf); 2] = [
-            (FileScope::Global, global_path),
-            (FileScope::Local, local_path),
-        ];
-
         self.load_items(
-            &search_paths,
-            "Stacks",
+            self.stack_files()?,
             "stack",
             "Local version of stack detected; using local definition.",
             |stack: &Stack| stack.name.clone(),
@@ -74,38 +45,130 @@ impl<FS: FileSystem, PP: PathProvider> ConfigLoader<FS, PP> {
     }
 
     pub fn load_services(&self) -> Result<HashMap<String, Service>> {
-        let local_path = self.path_provider.current_dir()?.join("services");
-        let global_path = self.path_provider.home_dir()?.join(".shoal/services");
-
-        let search_paths: [(FileScope, PathBuf); 2] = [
-            (FileScope::Global, global_path),
-            (FileScope::Local, local_path),
-        ];
-
         self.load_items(
-            &search_paths,
-            "Services",
+            self.service_files()?,
             "service",
             "Service override detected; using local definition.",
             |service: &Service| service.service_name.clone(),
         )
     }
+
+    /// Every override file, in load order, whether or not it parsed.
+    pub fn override_files(&self) -> Result<Vec<ConfigFile<StackOverride>>> {
+        self.scan_items(
+            "overrides",
+            "Overrides",
+            "overrides",
+            |stack_override: &mut StackOverride, source: &ConfigSource| {
+                stack_override.origin = Some(source.clone());
+                Ok(())
+            },
+        )
+    }
+
+    /// Every stack file, in load order, whether or not it parsed.
+    pub fn stack_files(&self) -> Result<Vec<ConfigFile<Stack>>> {
+        self.scan_items(
+            "stacks",
+            "Stacks",
+            "stack",
+            |stack: &mut Stack, source: &ConfigSource| {
+                stack.origin = Some(source.clone());
+                Ok(())
+            },
+        )
+    }
+
+    /// Every service file, in load order, whether or not it parsed.
+    pub fn service_files(&self) -> Result<Vec<ConfigFile<Service>>> {
+        self.scan_items(
+            "services",
+            "Services",
+            "service",
+            |service: &mut Service, source: &ConfigSource| {
+                if service.source.is_none() && service.compose.is_none() {
+                    bail!(ShoalError::ConfigParse {
+                        kind: "service",
+                        path: source.path.clone(),
+                        message: format!(
+                            "service '{}' must define either a source or a compose definition",
+                            service.service_name
+                        ),
+                    });
+                }
+                service.origin = Some(source.clone());
+                Ok(())
+            },
+        )
+    }
+}
+
+/// A config file found by the loader, with the item it defines or why it couldn't be loaded.
+pub struct ConfigFile<T> {
+    pub source: ConfigSource,
+    pub contents: String,
+    pub item: Result<T>,
+}
+
+trait Sourced {
+    fn origin(&self) -> Option<&ConfigSource>;
+    fn shadowed_mut(&mut self) -> &mut Vec<ConfigSource>;
+}
+
+impl Sourced for Service {
+    fn origin(&self) -> Option<&ConfigSource> {
+        self.origin.as_ref()
+    }
+
+    fn shadowed_mut(&mut self) -> &mut Vec<ConfigSource> {
+        &mut self.shadowed
+    }
+}
+
+impl Sourced for Stack {
+    fn origin(&self) -> Option<&ConfigSource> {
+        self.origin.as_ref()
+    }
+
+    fn shadowed_mut(&mut self) -> &mut Vec<ConfigSource> {
+        &mut self.shadowed
+    }
+}
+
+impl Sourced for StackOverride {
+    fn origin(&self) -> Option<&ConfigSource> {
+        self.origin.as_ref()
+    }
+
+    fn shadowed_mut(&mut self) -> &mut Vec<ConfigSource> {
+        &mut self.shadowed
+    }
 }
 
 impl<FS: FileSystem, PP: PathProvider> ConfigLoader<FS, PP> {
-    fn load_items<T, F>(
+    fn scan_items<T, L>(
         &self,
-        search_paths: &[(FileScope, PathBuf)],
+        folder: &str,
         folder_label: &'static str,
         item_label: &'static str,
-        override_message: &'static str,
-        name_extractor: F,
-    ) -> Result<HashMap<String, T>>
+        on_loaded: L,
+    ) -> Result<Vec<ConfigFile<T>>>
     where
         T: DeserializeOwned,
-        F: Fn(&T) -> String,
+        L: Fn(&mut T, &ConfigSource) -> Result<()>,
     {
-        for (scope, path) in search_paths {
+        let search_paths: [(FileScope, PathBuf); 2] = [
+            (
+                FileScope::Global,
+                self.path_provider.home_dir()?.join(".shoal").join(folder),
+            ),
+            (
+                FileScope::Local,
+                self.path_provider.current_dir()?.join(folder),
+            ),
+        ];
+
+        for (scope, path) in &search_paths {
             if self.file_system.exists(path) {
                 debug!(?path, %scope, "{} folder exists", folder_label);
             } else {
@@ -113,36 +176,74 @@ impl<FS: FileSystem, PP: PathProvider> ConfigLoader<FS, PP> {
             }
         }
 
-        let mut items_by_name: HashMap<String, (FileScope, T)> = HashMap::new();
-
-        for (scope, path) in search_paths {
+        let mut files = Vec::new();
+        for (scope, path) in &search_paths {
             if !self.file_system.exists(path) {
                 continue;
             }
 
             for (file_path, contents) in self.read_yaml_files_in_directory(path)? {
-                let item: T = serde_saphyr::from_str(&contents).with_context(|| {
-                    format!(
-                        "Failed to parse {} file: {}",
-                        item_label,
-                        file_path.display()
-                    )
-                })?;
-                let name = name_extractor(&item);
-
-                if let Some((previous_scope, _)) =
-                    items_by_name.insert(name.clone(), (*scope, item))
-                {
-                    warn!(
-                        service = %name,
-                        previous = %previous_scope,
-                        current = %scope,
-                        "{}", override_message
-                    );
-                } else {
-                    debug!(service = %name, source = %scope, "Loaded {}", item_label);
-                }
+                let source = ConfigSource {
+                    scope: *scope,
+                    path: file_path.clone(),
+                };
+                let item = serde_saphyr::from_str::<T>(&contents)
+                    .map_err(|e| {
+                        ShoalError::ConfigParse {
+                            kind: item_label,
+                            path: file_path,
+                            message: e.to_string(),
+                        }
+                        .into()
+                    })
+                    .and_then(|mut item| {
+                        on_loaded(&mut item, &source)?;
+                        Ok(item)
+                    });
+
+                files.push(ConfigFile {
+                    source,
+                    contents,
+                    item,
+                });
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn load_items<T, F>(
+        &self,
+        files: Vec<ConfigFile<T>>,
+        item_label: &'static str,
+        override_message: &'static str,
+        name_extractor: F,
+    ) -> Result<HashMap<String, T>>
+    where
+        T: Sourced,
+        F: Fn(&T) -> String,
+    {
+        let mut items_by_name: HashMap<String, (FileScope, T)> = HashMap::new();
+
+        for file in files {
+            let mut item = file.item?;
+            let scope = file.source.scope;
+            let name = name_extractor(&item);
+
+            if let Some((previous_scope, mut previous)) = items_by_name.remove(&name) {
+                warn!(
+                    service = %name,
+                    previous = %previous_scope,
+                    current = %scope,
+                    "{}", override_message
+                );
+                let mut shadowed = std::mem::take(previous.shadowed_mut());
+                shadowed.extend(previous.origin().cloned());
+                *item.shadowed_mut() = shadowed;
+            } else {
+                debug!(service = %name, source = %scope, "Loaded {}", item_label);
             }
+            items_by_name.insert(name, (scope, item));
         }
 
         let items: HashMap<String, T> = items_by_name
@@ -156,10 +257,12 @@ impl<FS: FileSystem, PP: PathProvider> ConfigLoader<FS, PP> {
     }
 
     fn read_yaml_files_in_directory(&self, path: &Path) -> Result<Vec<(PathBuf, String)>> {
-        let entries = self
+        let mut entries = self
             .file_system
             .read_dir(path)
             .with_context(|| format!("Failed to read directory: {}", path.display()))?;
+        // Directory order is platform dependent; sorting keeps loading deterministic.
+        entries.sort();
 
         let mut result = Vec::new();
         for file_path in entries {
@@ -1,9 +1,14 @@
 use std::{path::PathBuf, sync::Arc};
 
-use anyhow::Result;
-use tracing::info;
+use anyhow::{Context, Result, bail};
+use tracing::{debug, info};
 
-use crate::traits::CommandExecutor;
+use crate::{
+    traits::{CommandExecutor, OutputLine, OutputStream},
+    types::{logs::LogOptions, stack_status::ContainerStatus},
+};
+
+const SHELLS: [&str; 3] = ["bash", "sh", "ash"];
 
 pub struct ComposeManager {
     compose_file_path: PathBuf,
@@ -27,37 +32,259 @@ impl ComposeManager {
     pub fn up(&self) -> Result<()> {
         info!("Starting container stack.");
 
-        let compose_path_str = self.compose_file_path.to_string_lossy().to_string();
-        let args = [
-            "compose",
-            "--project-name",
-            &self.project_name,
-            "-f",
-            &compose_path_str,
-            "up",
-            "-d",
-            "--build",
-            "--wait",
-        ];
+        let mut args = self.base_args();
+        args.extend(
+            ["up", "-d", "--build", "--wait"]
+                .into_iter()
+                .map(String::from),
+        );
 
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
         self.command_executor.execute("docker", &args)?;
         info!("Started all containers successfully.");
         Ok(())
     }
 
     pub fn down(&self) -> Result<()> {
-        let compose_path_str = self.compose_file_path.to_string_lossy().to_string();
-        let args = [
-            "compose",
-            "--project-name",
-            &self.project_name,
-            "-f",
-            &compose_path_str,
-            "down",
-        ];
+        let mut args = self.base_args();
+        args.push("down".to_string());
 
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
         self.command_executor.execute("docker", &args)?;
         info!("Stopped all containers successfully.");
         Ok(())
     }
+
+    pub fn ps(&self) -> Result<Vec<ContainerStatus>> {
+        let mut args = self.base_args();
+        args.extend(
+            ["ps", "--all", "--format", "json"]
+                .into_iter()
+                .map(String::from),
+        );
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self
+            .command_executor
+            .capture("docker", &args)?
+            .into_stdout("docker")?;
+        debug!("docker compose ps returned {} bytes", output.len());
+        parse_ps_output(&output)
+    }
+
+    /// Streams `docker compose logs`, splitting the compose prefix from each line.
+    pub fn logs(
+        &self,
+        options: &LogOptions,
+        on_line: &mut dyn FnMut(Option<&str>, &str, OutputStream),
+    ) -> Result<()> {
+        let mut args = self.base_args();
+        args.extend(["logs".to_string(), "--no-color".to_string()]);
+        if options.follow {
+            args.push("--follow".to_string());
+        }
+        if let Some(since) = &options.since {
+            args.extend(["--since".to_string(), since.clone()]);
+        }
+        if let Some(tail) = &options.tail {
+            args.extend(["--tail".to_string(), tail.clone()]);
+        }
+        args.extend(options.services.iter().cloned());
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.command_executor
+            .stream("docker", &args, &mut |line: OutputLine| match (
+                line.stream,
+                split_log_prefix(&line.text),
+            ) {
+                (OutputStream::Stdout, Some((prefix, message))) => {
+                    on_line(Some(prefix), message, line.stream)
+                }
+                _ => on_line(None, &line.text, line.stream),
+            })
+    }
+
+    pub fn restart(&self, services: &[String]) -> Result<()> {
+        let mut args = self.base_args();
+        args.push("restart".to_string());
+        args.extend(services.iter().cloned());
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.command_executor.execute("docker", &args)?;
+        info!("Restarted {}.", services.join(", "));
+        Ok(())
+    }
+
+    /// Rebuilds `build`, then recreates `recreate` without touching their dependencies.
+    pub fn rebuild(&self, build: &[String], recreate: &[String]) -> Result<()> {
+        let mut args = self.base_args();
+        args.push("build".to_string());
+        args.extend(build.iter().cloned());
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.command_executor.execute("docker", &args)?;
+
+        let mut args = self.base_args();
+        args.extend(
+            ["up", "-d", "--no-deps", "--force-recreate", "--wait"]
+                .into_iter()
+                .map(String::from),
+        );
+        args.extend(recreate.iter().cloned());
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.command_executor.execute("docker", &args)?;
+        info!("Recreated {}.", recreate.join(", "));
+        Ok(())
+    }
+
+    /// Runs a command in the service's running container with the terminal attached.
+    pub fn exec(&self, service: &str, command: &[String]) -> Result<()> {
+        let mut args = self.base_args();
+        args.extend(["exec".to_string(), service.to_string()]);
+        args.extend(command.iter().cloned());
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.command_executor.execute("docker", &args)
+    }
+
+    /// Finds the first of `SHELLS` the service's container can run.
+    pub fn find_shell(&self, service: &str) -> Result<&'static str> {
+        for shell in SHELLS {
+            let mut args = self.base_args();
+            args.extend(
+                ["exec", "-T", service, shell, "-c", "exit 0"]
+                    .into_iter()
+                    .map(String::from),
+            );
+
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let output = self.command_executor.capture("docker", &args)?;
+            if output.success() {
+                debug!("Using {shell} for {service}");
+                return Ok(shell);
+            }
+            // 126 and 127 mean the shell could not be found or run; anything else is a
+            // problem with the container itself, such as it not running.
+            if !matches!(output.code, Some(126 | 127)) {
+                bail!("Failed to exec into {service}: {}", output.stderr.trim());
+            }
+        }
+
+        bail!("None of {} is available in {service}", SHELLS.join(", "))
+    }
+
+    fn base_args(&self) -> Vec<String> {
+        vec![
+            "compose".to_string(),
+            "--project-name".to_string(),
+            self.project_name.clone(),
+            "-f".to_string(),
+            self.compose_file_path.to_string_lossy().to_string(),
+        ]
+    }
+}
+
+fn split_log_prefix(line: &str) -> Option<(&str, &str)> {
+    let (prefix, message) = line.split_once(" | ").or_else(|| {
+        // Empty log lines are printed without the trailing space.
+        line.strip_suffix(" |").map(|prefix| (prefix, ""))
+    })?;
+    let prefix = prefix.trim();
+    (!prefix.is_empty() && !prefix.contains(char::is_whitespace)).then_some((prefix, message))
+}
+
+fn parse_ps_output(output: &str) -> Result<Vec<ContainerStatus>> {
+    let output = output.trim();
+    if output.starts_with('[') {
+        return serde_json::from_str(output).context("Failed to parse docker compose ps output");
+    }
+
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).context("Failed to parse docker compose ps output"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::{CommandOutput, mocks::MockCommandExecutor};
+
+    #[test]
+    fn test_parse_ps_output_formats() {
+        let line = r#"{"Name":"full-postgres","Service":"postgres","State":"running","Health":"healthy","RunningFor":"5 minutes ago","Publishers":[{"URL":"0.0.0.0","TargetPort":5432,"PublishedPort":5432,"Protocol":"tcp"}]}"#;
+
+        let from_lines = parse_ps_output(&format!("{line}\n{line}\n")).unwrap();
+        assert_eq!(from_lines.len(), 2);
+        assert_eq!(from_lines[0].service, "postgres");
+        assert_eq!(
+            from_lines[0].publishers.as_ref().unwrap()[0].published_port,
+            5432
+        );
+
+        let from_array = parse_ps_output(&format!("[{line}]")).unwrap();
+        assert_eq!(from_array[0].health, "healthy");
+
+        assert!(parse_ps_output("").unwrap().is_empty());
+    }
+
+    fn compose_manager(command_executor: Arc<MockCommandExecutor>) -> ComposeManager {
+        ComposeManager::new("/test/compose.yml", "full", command_executor)
+    }
+
+    #[test]
+    fn test_find_shell_falls_back_when_missing() {
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        command_executor.push_output(CommandOutput {
+            code: Some(127),
+            ..Default::default()
+        });
+        command_executor.push_stdout("");
+
+        let shell = compose_manager(command_executor.clone())
+            .find_shell("postgres")
+            .unwrap();
+        assert_eq!(shell, "sh");
+
+        let calls = command_executor.calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].1.ends_with(&[
+            "postgres".to_string(),
+            "bash".to_string(),
+            "-c".to_string(),
+            "exit 0".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_find_shell_stops_when_container_is_not_running() {
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        command_executor.push_output(CommandOutput {
+            stderr: "service \"postgres\" is not running".to_string(),
+            code: Some(1),
+            ..Default::default()
+        });
+
+        let error = compose_manager(command_executor.clone())
+            .find_shell("postgres")
+            .unwrap_err();
+        assert!(error.to_string().contains("is not running"));
+        assert_eq!(command_executor.calls.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_split_log_prefix() {
+        assert_eq!(
+            split_log_prefix("full-postgres  | ready to accept connections"),
+            Some(("full-postgres", "ready to accept connections"))
+        );
+        assert_eq!(
+            split_log_prefix("backend-1 | a | b"),
+            Some(("backend-1", "a | b"))
+        );
+        assert_eq!(split_log_prefix("backend-1 |"), Some(("backend-1", "")));
+        assert_eq!(split_log_prefix("no such service: web"), None);
+    }
 }
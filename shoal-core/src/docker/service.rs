@@ -1,18 +1,597 @@
-use crate::types::{docker_service::DockerService, service::Service};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use crate::types::{
+    docker_service::{DependencyCondition, DependsOn, DockerBuild, DockerService, HealthCheck},
+    service::{LocationType, Service},
+};
+
+/// Dockerfiles picked up automatically for local services, in order of preference.
+pub const DOCKERFILE_CANDIDATES: [&str; 2] = ["Dockerfile.shoal", "Dockerfile.dev"];
+
+const MANAGED_COMPOSE_KEYS: [&str; 3] = ["container_name", "networks", "depends_on"];
 
 pub fn build_docker_service(service: &Service, stack_name: &str, network: &str) -> DockerService {
+    let mut docker_service = service
+        .compose
+        .as_ref()
+        .map(|definition| from_compose_definition(service, definition))
+        .unwrap_or_default();
+
+    if let Some(source) = &service.source {
+        match source.r#type {
+            LocationType::Image => {
+                docker_service.extra.remove("image");
+                docker_service.image = Some(source.location.clone());
+            }
+            LocationType::Local => {
+                docker_service.extra.remove("image");
+                docker_service.extra.remove("build");
+                docker_service.image = None;
+                docker_service.build = Some(DockerBuild {
+                    context: service
+                        .resolve_path(&source.location)
+                        .to_string_lossy()
+                        .to_string(),
+                    dockerfile: source.dockerfile.clone(),
+                });
+            }
+        }
+    }
+
+    if !service.internal_ports.is_empty() {
+        if docker_service.extra.contains_key("ports") {
+            warn!(
+                service = %service.service_name,
+                "Compose ports use long syntax; internal_ports are ignored."
+            );
+        } else {
+            let mut ports = docker_service.ports.take().unwrap_or_default();
+            ports.extend(service.internal_ports.iter().cloned());
+            docker_service.ports = Some(ports);
+        }
+    }
+
+    apply_service_fields(service, &mut docker_service);
+
+    docker_service.container_name = format!("{}-{}", stack_name, service.service_name);
+    docker_service.networks = Some(vec![network.to_string()]);
+    docker_service.depends_on = if service.dependencies.is_empty() {
+        None
+    } else {
+        Some(
+            service
+                .dependencies
+                .iter()
+                .map(|dependency| {
+                    (
+                        dependency.clone(),
+                        DependsOn {
+                            condition: DependencyCondition::Started,
+                        },
+                    )
+                })
+                .collect(),
+        )
+    };
+
+    docker_service
+}
+
+fn apply_service_fields(service: &Service, docker_service: &mut DockerService) {
+    if !service.env.is_empty() {
+        docker_service.merge_environment(&service.env);
+    }
+
+    if !service.env_file.is_empty() {
+        let env_files: Vec<String> = service
+            .env_file
+            .iter()
+            .map(|file| service.resolve_path(file).to_string_lossy().to_string())
+            .collect();
+        if !docker_service.append_passthrough("env_file", &env_files) {
+            let mut env_file = docker_service.env_file.take().unwrap_or_default();
+            env_file.extend(env_files);
+            docker_service.env_file = Some(env_file);
+        }
+    }
+
+    if !service.volumes.is_empty() {
+        let service_volumes: Vec<String> = service
+            .volumes
+            .iter()
+            .map(|volume| service.resolve_volume(volume))
+            .collect();
+        if !docker_service.append_passthrough("volumes", &service_volumes) {
+            let mut volumes = docker_service.volumes.take().unwrap_or_default();
+            volumes.extend(service_volumes);
+            docker_service.volumes = Some(volumes);
+        }
+    }
+
+    if let Some(command) = &service.command {
+        docker_service.extra.remove("command");
+        docker_service.command = Some(command.clone());
+    }
+
+    if let Some(entrypoint) = &service.entrypoint {
+        docker_service.extra.remove("entrypoint");
+        docker_service.entrypoint = Some(entrypoint.clone());
+    }
+
+    if service.restart.is_some() {
+        docker_service.restart = service.restart.clone();
+    }
+
+    if service.working_dir.is_some() {
+        docker_service.working_dir = service.working_dir.clone();
+    }
+
+    if service.healthcheck.is_some() {
+        docker_service.extra.remove("healthcheck");
+        docker_service.healthcheck = service.healthcheck.clone();
+    }
+}
+
+/// Makes services wait for dependencies with a healthcheck to report healthy.
+pub fn gate_dependencies_on_health(docker_services: &mut HashMap<String, DockerService>) {
+    let healthy: Vec<String> = docker_services
+        .iter()
+        .filter(|(_, service)| {
+            service
+                .healthcheck
+                .as_ref()
+                .is_some_and(HealthCheck::is_enabled)
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for depends_on in docker_services
+        .values_mut()
+        .filter_map(|service| service.depends_on.as_mut())
+    {
+        for (dependency, depends) in depends_on.iter_mut() {
+            if healthy.contains(dependency) {
+                depends.condition = DependencyCondition::Healthy;
+            }
+        }
+    }
+}
+
+fn from_compose_definition(service: &Service, definition: &Map<String, Value>) -> DockerService {
+    let mut extra: BTreeMap<String, Value> = definition
+        .iter()
+        .filter(|(key, _)| !MANAGED_COMPOSE_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    resolve_compose_paths(service, &mut extra);
+
     DockerService {
-        container_name: format!("{}-{}", stack_name, service.service_name),
-        image: Some(service.source.location.clone()),
-        ports: Some(service.internal_ports.clone()),
-        networks: Some(vec![network.to_string()]),
-        build_context: None,
-        dockerfile: None,
-        entrypoint: None,
-        command: None,
-        environment: None,
-        volumes: None,
-        depends_on: None,
-        restart: None,
+        image: take_lifted(&mut extra, "image", |v| v.as_str().map(str::to_string)),
+        entrypoint: take_lifted(&mut extra, "entrypoint", string_list),
+        command: take_lifted(&mut extra, "command", string_list),
+        environment: take_lifted(&mut extra, "environment", environment_map),
+        env_file: take_lifted(&mut extra, "env_file", string_list),
+        ports: take_lifted(&mut extra, "ports", string_list),
+        volumes: take_lifted(&mut extra, "volumes", string_list),
+        restart: take_lifted(&mut extra, "restart", |v| v.as_str().map(str::to_string)),
+        healthcheck: take_lifted(&mut extra, "healthcheck", |v| {
+            serde_json::from_value(v.clone()).ok()
+        }),
+        working_dir: take_lifted(&mut extra, "working_dir", |v| {
+            v.as_str().map(str::to_string)
+        }),
+        extra,
+        ..DockerService::default()
+    }
+}
+
+fn resolve_compose_paths(service: &Service, extra: &mut BTreeMap<String, Value>) {
+    let resolve = |path: &mut String| {
+        *path = service.resolve_path(path).to_string_lossy().to_string();
+    };
+
+    match extra.get_mut("build") {
+        Some(Value::String(context)) => resolve(context),
+        Some(Value::Object(build)) => {
+            if let Some(Value::String(context)) = build.get_mut("context") {
+                resolve(context);
+            }
+        }
+        _ => {}
+    }
+
+    match extra.get_mut("env_file") {
+        Some(Value::String(file)) => resolve(file),
+        Some(Value::Array(files)) => {
+            for file in files {
+                match file {
+                    Value::String(file) => resolve(file),
+                    Value::Object(file) => {
+                        if let Some(Value::String(path)) = file.get_mut("path") {
+                            resolve(path);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(volumes)) = extra.get_mut("volumes") {
+        for volume in volumes {
+            match volume {
+                Value::String(volume) => *volume = service.resolve_volume(volume),
+                Value::Object(volume) => {
+                    if let Some(Value::String(source)) = volume.get_mut("source")
+                        && source.starts_with('.')
+                    {
+                        resolve(source);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn take_lifted<T>(
+    extra: &mut BTreeMap<String, Value>,
+    key: &str,
+    lift: impl Fn(&Value) -> Option<T>,
+) -> Option<T> {
+    let lifted = extra.get(key).and_then(lift)?;
+    extra.remove(key);
+    Some(lifted)
+}
+
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn string_list(value: &Value) -> Option<Vec<String>> {
+    value.as_array()?.iter().map(scalar_string).collect()
+}
+
+fn environment_map(value: &Value) -> Option<HashMap<String, String>> {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| Some((key.clone(), scalar_string(value)?)))
+            .collect(),
+        Value::Array(entries) => entries
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry.as_str()?.split_once('=')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+pub fn detect_dockerfile(context: &Path, exists: impl Fn(&Path) -> bool) -> Option<String> {
+    DOCKERFILE_CANDIDATES
+        .iter()
+        .find(|candidate| exists(&context.join(candidate)))
+        .map(|candidate| candidate.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        config_source::{ConfigSource, FileScope},
+        docker_service::HealthCheckTest,
+        service::ServiceLocation,
+    };
+    use std::path::PathBuf;
+
+    fn create_local_service(location: &str) -> Service {
+        Service {
+            service_name: "backend".to_string(),
+            source: Some(ServiceLocation {
+                r#type: LocationType::Local,
+                location: location.to_string(),
+                dockerfile: None,
+            }),
+            origin: Some(ConfigSource {
+                scope: FileScope::Local,
+                path: PathBuf::from("/org/services/backend.yml"),
+            }),
+            ..Service::default()
+        }
+    }
+
+    #[test]
+    fn test_build_local_service_relative_to_definition() {
+        let service = create_local_service("../../backend");
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        assert!(docker_service.image.is_none());
+        let build = docker_service.build.unwrap();
+        assert_eq!(build.context, "/org/services/../../backend");
+        assert_eq!(build.dockerfile, None);
+    }
+
+    #[test]
+    fn test_build_local_service_absolute_path() {
+        let service = create_local_service("/src/backend");
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        assert_eq!(docker_service.build.unwrap().context, "/src/backend");
+    }
+
+    #[test]
+    fn test_source_replaces_compose_build_and_image() {
+        let compose = r#"
+service_name: api
+source:
+  type: Local
+  location: /src/api
+compose:
+  image:
+    name: api
+  build:
+    context: ./api
+    target: dev
+"#;
+        let service: Service = serde_saphyr::from_str(compose).unwrap();
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        assert_eq!(docker_service.build.as_ref().unwrap().context, "/src/api");
+        assert!(docker_service.image.is_none());
+        assert!(docker_service.extra.is_empty());
+        let rendered = serde_json::to_value(&docker_service).unwrap();
+        assert_eq!(rendered["build"]["context"], "/src/api");
+    }
+
+    #[test]
+    fn test_build_service_maps_condensed_fields() {
+        let definition = r#"
+service_name: postgres
+source:
+  type: Image
+  location: postgres:latest
+env:
+  POSTGRES_USER: postgres
+env_file:
+  - ./postgres.env
+volumes:
+  - ./init:/docker-entrypoint-initdb.d
+  - pgdata:/var/lib/postgresql/data
+command: ["postgres", "-c", "log_statement=all"]
+restart: unless-stopped
+working_dir: /srv
+"#;
+        let mut service: Service = serde_saphyr::from_str(definition).unwrap();
+        service.origin = Some(ConfigSource {
+            scope: FileScope::Local,
+            path: PathBuf::from("/org/services/postgres.yml"),
+        });
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        let environment = docker_service.environment.unwrap();
+        assert_eq!(
+            environment.get("POSTGRES_USER"),
+            Some(&"postgres".to_string())
+        );
+        assert_eq!(
+            docker_service.env_file,
+            Some(vec!["/org/services/./postgres.env".to_string()])
+        );
+        assert_eq!(
+            docker_service.volumes,
+            Some(vec![
+                "/org/services/./init:/docker-entrypoint-initdb.d".to_string(),
+                "pgdata:/var/lib/postgresql/data".to_string(),
+            ])
+        );
+        assert_eq!(docker_service.command.unwrap().len(), 3);
+        assert_eq!(docker_service.restart, Some("unless-stopped".to_string()));
+        assert_eq!(docker_service.working_dir, Some("/srv".to_string()));
+    }
+
+    #[test]
+    fn test_build_compose_service_passes_through() {
+        let compose = r#"
+service_name: cache
+dependencies:
+  - postgres
+compose:
+  image: redis:7
+  container_name: my-cache
+  networks:
+    - other
+  environment:
+    - MODE=dev
+    - RETRIES=3
+  ports:
+    - 6379
+  healthcheck:
+    test: ["CMD", "redis-cli", "ping"]
+    interval: 5s
+  ulimits:
+    nofile: 65536
+  labels:
+    team: platform
+"#;
+        let service: Service = serde_saphyr::from_str(compose).unwrap();
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        assert_eq!(docker_service.container_name, "stack-cache");
+        assert_eq!(docker_service.image, Some("redis:7".to_string()));
+        assert_eq!(
+            docker_service.networks,
+            Some(vec!["stack-network".to_string()])
+        );
+        assert!(docker_service.depends_on.unwrap().contains_key("postgres"));
+        assert_eq!(docker_service.ports, Some(vec!["6379".to_string()]));
+        let environment = docker_service.environment.as_ref().unwrap();
+        assert_eq!(environment.get("MODE"), Some(&"dev".to_string()));
+        assert_eq!(environment.get("RETRIES"), Some(&"3".to_string()));
+
+        let healthcheck = docker_service.healthcheck.unwrap();
+        assert_eq!(healthcheck.interval, Some("5s".to_string()));
+        let keys: Vec<&String> = docker_service.extra.keys().collect();
+        assert_eq!(keys, vec!["labels", "ulimits"]);
+        assert_eq!(docker_service.extra["ulimits"]["nofile"], 65536);
+    }
+
+    #[test]
+    fn test_build_compose_service_keeps_unliftable_syntax() {
+        let compose = r#"
+service_name: api
+compose:
+  image: api:latest
+  command: npm run dev
+  ports:
+    - target: 80
+      published: 8080
+"#;
+        let service: Service = serde_saphyr::from_str(compose).unwrap();
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        assert!(docker_service.command.is_none());
+        assert_eq!(docker_service.extra["command"], "npm run dev");
+        assert!(docker_service.ports.is_none());
+        assert!(docker_service.extra.contains_key("ports"));
+    }
+
+    #[test]
+    fn test_service_fields_merge_into_unliftable_compose_keys() {
+        let compose = r#"
+service_name: api
+env:
+  A: b
+env_file:
+  - ./api.env
+volumes:
+  - ./src:/src
+compose:
+  image: api:latest
+  build:
+    context: ./api
+  environment:
+    - FOO
+    - A=old
+  env_file: .env
+  volumes:
+    - type: volume
+      source: data
+      target: /data
+    - type: bind
+      source: ./config
+      target: /config
+"#;
+        let mut service: Service = serde_saphyr::from_str(compose).unwrap();
+        service.origin = Some(ConfigSource {
+            scope: FileScope::Local,
+            path: PathBuf::from("/org/services/api.yml"),
+        });
+        let docker_service = build_docker_service(&service, "stack", "stack-network");
+
+        assert!(docker_service.environment.is_none());
+        assert_eq!(
+            docker_service.extra["environment"],
+            serde_json::json!(["FOO", "A=b"])
+        );
+        assert!(docker_service.env_file.is_none());
+        assert_eq!(
+            docker_service.extra["env_file"],
+            serde_json::json!(["/org/services/.env", "/org/services/./api.env"])
+        );
+        assert!(docker_service.volumes.is_none());
+        assert_eq!(docker_service.extra["volumes"][0]["source"], "data");
+        assert_eq!(
+            docker_service.extra["volumes"][1]["source"],
+            "/org/services/./config"
+        );
+        assert_eq!(
+            docker_service.extra["volumes"][2],
+            "/org/services/./src:/src"
+        );
+        assert_eq!(
+            docker_service.extra["build"]["context"],
+            "/org/services/./api"
+        );
+
+        let rendered = serde_json::to_value(&docker_service).unwrap();
+        assert_eq!(rendered["environment"], serde_json::json!(["FOO", "A=b"]));
+    }
+
+    #[test]
+    fn test_healthcheck_gates_dependents() {
+        let postgres = r#"
+service_name: postgres
+source:
+  type: Image
+  location: postgres:latest
+healthcheck:
+  test: pg_isready -U postgres
+  interval: 2s
+  retries: 10
+"#;
+        let postgres: Service = serde_saphyr::from_str(postgres).unwrap();
+        let redis = Service {
+            service_name: "redis".to_string(),
+            ..Service::default()
+        };
+        let backend = Service {
+            service_name: "backend".to_string(),
+            dependencies: vec!["postgres".to_string(), "redis".to_string()],
+            ..Service::default()
+        };
+
+        let mut docker_services: HashMap<String, DockerService> = [postgres, redis, backend]
+            .iter()
+            .map(|service| {
+                (
+                    service.service_name.clone(),
+                    build_docker_service(service, "stack", "stack-network"),
+                )
+            })
+            .collect();
+        gate_dependencies_on_health(&mut docker_services);
+
+        let depends_on = docker_services["backend"].depends_on.as_ref().unwrap();
+        assert_eq!(
+            depends_on["postgres"].condition,
+            DependencyCondition::Healthy
+        );
+        assert_eq!(depends_on["redis"].condition, DependencyCondition::Started);
+        assert_eq!(
+            docker_services["postgres"]
+                .healthcheck
+                .as_ref()
+                .unwrap()
+                .test,
+            Some(HealthCheckTest::Shell("pg_isready -U postgres".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_detect_dockerfile_prefers_shoal() {
+        let context = Path::new("/src/backend");
+        let dockerfile = detect_dockerfile(context, |path| {
+            path.ends_with("Dockerfile.dev") || path.ends_with("Dockerfile.shoal")
+        });
+        assert_eq!(dockerfile, Some("Dockerfile.shoal".to_string()));
+
+        let dockerfile = detect_dockerfile(context, |path| path.ends_with("Dockerfile.dev"));
+        assert_eq!(dockerfile, Some("Dockerfile.dev".to_string()));
+
+        assert_eq!(detect_dockerfile(context, |_| false), None);
     }
 }
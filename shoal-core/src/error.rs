@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, ShoalError>;
+
+/// Errors surfaced by the public Shoal API.
+#[derive(Debug, Error)]
+pub enum ShoalError {
+    #[error("Failed to parse {kind} file {}: {message}", path.display())]
+    ConfigParse {
+        kind: &'static str,
+        path: PathBuf,
+        message: String,
+    },
+
+    #[error("Failed to find a stack with the name '{stack}'.")]
+    UnknownStack { stack: String },
+
+    #[error("Failed to find an override for {stack} with the name '{name}'.")]
+    UnknownOverride { stack: String, name: String },
+
+    #[error("{referenced_by} references non-existent services: {services:?}")]
+    MissingService {
+        referenced_by: String,
+        services: Vec<String>,
+    },
+
+    #[error("Dependency cycle detected: {}", path.join(" -> "))]
+    DependencyCycle { path: Vec<String> },
+
+    #[error("Stack {stack} is not running; compose file missing at {}", compose_path.display())]
+    StackNotRunning {
+        stack: String,
+        compose_path: PathBuf,
+    },
+
+    #[error("Command '{command}' failed with exit code: {code:?}")]
+    CommandFailed { command: String, code: Option<i32> },
+
+    #[error("Configuration is invalid: {errors} error(s) found")]
+    InvalidConfig { errors: usize },
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ShoalError {
+    fn from(error: anyhow::Error) -> Self {
+        error
+            .downcast::<ShoalError>()
+            .unwrap_or_else(ShoalError::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_from_anyhow_recovers_typed_error() {
+        let error: anyhow::Result<()> = Err(ShoalError::UnknownStack {
+            stack: "full-stack".to_string(),
+        })
+        .context("Failed to bring stack up");
+
+        let error = ShoalError::from(error.unwrap_err());
+        assert!(matches!(error, ShoalError::UnknownStack { stack } if stack == "full-stack"));
+    }
+
+    #[test]
+    fn test_from_anyhow_wraps_untyped_error() {
+        let error = ShoalError::from(anyhow::anyhow!("disk full"));
+        assert!(matches!(error, ShoalError::Other(_)));
+        assert_eq!(error.to_string(), "disk full");
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::types::{
+    docker_service::DockerComposeFile,
+    graph::{GraphEdge, GraphNode, NodeRole, NodeSource, ServiceGraph},
+    service::{LocationType, Service},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+/// Builds the graph of a generated stack, including services it excluded.
+pub fn build_graph(
+    stack_name: &str,
+    roots: &[String],
+    compose: &DockerComposeFile,
+    services: &HashMap<String, Service>,
+) -> ServiceGraph {
+    let mut reached = BTreeSet::new();
+    let mut pending: Vec<&String> = roots.iter().collect();
+    while let Some(name) = pending.pop() {
+        if !reached.insert(name.clone()) {
+            continue;
+        }
+        if let Some(service) = services.get(name) {
+            pending.extend(&service.dependencies);
+        }
+    }
+
+    // Services can also end up in the compose file without being reachable from the
+    // stack's own list, so anything unaccounted for is treated as a root too.
+    let mut names: BTreeSet<String> = reached.iter().cloned().collect();
+    names.extend(compose.services.keys().cloned());
+
+    let nodes = names
+        .iter()
+        .map(|name| {
+            let role = match compose.services.get(name) {
+                None => NodeRole::Excluded,
+                Some(_) if roots.contains(name) || !reached.contains(name) => NodeRole::Root,
+                Some(_) => NodeRole::Dependency,
+            };
+            let local = match compose.services.get(name) {
+                Some(docker_service) => docker_service.build.is_some(),
+                None => services
+                    .get(name)
+                    .and_then(|service| service.source.as_ref())
+                    .is_some_and(|source| matches!(source.r#type, LocationType::Local)),
+            };
+            GraphNode {
+                name: name.clone(),
+                role,
+                source: if local {
+                    NodeSource::Local
+                } else {
+                    NodeSource::Image
+                },
+            }
+        })
+        .collect();
+
+    let mut edges = BTreeSet::new();
+    for (name, docker_service) in &compose.services {
+        for dependency in docker_service.depends_on.iter().flatten().map(|(d, _)| d) {
+            edges.insert(GraphEdge {
+                from: name.clone(),
+                to: dependency.clone(),
+                excluded: false,
+            });
+        }
+    }
+    for name in &reached {
+        let Some(service) = services.get(name) else {
+            continue;
+        };
+        for dependency in &service.dependencies {
+            let excluded =
+                !compose.services.contains_key(name) || !compose.services.contains_key(dependency);
+            if excluded && names.contains(dependency) {
+                edges.insert(GraphEdge {
+                    from: name.clone(),
+                    to: dependency.clone(),
+                    excluded,
+                });
+            }
+        }
+    }
+
+    ServiceGraph {
+        stack: stack_name.to_string(),
+        nodes,
+        edges: edges.into_iter().collect(),
+    }
+}
+
+pub fn render_graph(graph: &ServiceGraph, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => render_dot(graph),
+        GraphFormat::Mermaid => render_mermaid(graph),
+    }
+}
+
+fn render_dot(graph: &ServiceGraph) -> String {
+    let mut lines = vec![
+        format!("digraph \"{}\" {{", escape_dot(&graph.stack)),
+        "  rankdir=LR;".to_string(),
+    ];
+
+    for node in &graph.nodes {
+        let shape = match node.source {
+            NodeSource::Local => "box",
+            NodeSource::Image => "ellipse",
+        };
+        let style = match node.role {
+            NodeRole::Root => ", style=bold, penwidth=2",
+            NodeRole::Dependency => "",
+            NodeRole::Excluded => ", style=dashed, color=gray, fontcolor=gray",
+        };
+        lines.push(format!(
+            "  \"{}\" [shape={shape}{style}];",
+            escape_dot(&node.name)
+        ));
+    }
+
+    for edge in &graph.edges {
+        let style = if edge.excluded {
+            " [style=dashed, color=gray]"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "  \"{}\" -> \"{}\"{style};",
+            escape_dot(&edge.from),
+            escape_dot(&edge.to)
+        ));
+    }
+
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn render_mermaid(graph: &ServiceGraph) -> String {
+    let mut lines = vec!["flowchart LR".to_string()];
+
+    for node in &graph.nodes {
+        let id = mermaid_id(&node.name);
+        let label = node.name.replace('"', "#quot;");
+        lines.push(match node.source {
+            NodeSource::Local => format!("  {id}[\"{label}\"]"),
+            NodeSource::Image => format!("  {id}([\"{label}\"])"),
+        });
+    }
+
+    for edge in &graph.edges {
+        let arrow = if edge.excluded { "-.->" } else { "-->" };
+        lines.push(format!(
+            "  {} {arrow} {}",
+            mermaid_id(&edge.from),
+            mermaid_id(&edge.to)
+        ));
+    }
+
+    lines.push("  classDef root stroke-width:3px".to_string());
+    lines.push("  classDef excluded stroke-dasharray:5 5,color:#999".to_string());
+    for (class, role) in [("root", NodeRole::Root), ("excluded", NodeRole::Excluded)] {
+        let ids: Vec<String> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.role == role)
+            .map(|node| mermaid_id(&node.name))
+            .collect();
+        if !ids.is_empty() {
+            lines.push(format!("  class {} {class}", ids.join(",")));
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("svc_{id}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::service::build_docker_service;
+    use crate::types::service::ServiceLocation;
+
+    fn service(name: &str, location_type: LocationType, dependencies: &[&str]) -> Service {
+        Service {
+            service_name: name.to_string(),
+            source: Some(ServiceLocation {
+                r#type: location_type,
+                location: name.to_string(),
+                dockerfile: None,
+            }),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn example_graph() -> ServiceGraph {
+        let mut services = HashMap::new();
+        services.insert(
+            "frontend".to_string(),
+            service("frontend", LocationType::Local, &["backend", "metrics"]),
+        );
+        services.insert(
+            "backend".to_string(),
+            service("backend", LocationType::Local, &["postgres"]),
+        );
+        services.insert(
+            "postgres".to_string(),
+            service("postgres", LocationType::Image, &[]),
+        );
+        services.insert(
+            "metrics".to_string(),
+            service("metrics", LocationType::Image, &["postgres"]),
+        );
+
+        // What the stack manager generates with `metrics` excluded.
+        let mut compose_services = HashMap::new();
+        for name in ["frontend", "backend", "postgres"] {
+            let mut docker_service = build_docker_service(&services[name], "full", "net");
+            if let Some(depends_on) = docker_service.depends_on.as_mut() {
+                depends_on.retain(|dependency, _| dependency != "metrics");
+            }
+            compose_services.insert(name.to_string(), docker_service);
+        }
+        let compose = DockerComposeFile {
+            services: compose_services,
+            networks: HashMap::new(),
+        };
+
+        build_graph("full", &["frontend".to_string()], &compose, &services)
+    }
+
+    #[test]
+    fn test_build_graph_marks_roles_and_sources() {
+        let graph = example_graph();
+
+        let node = |name: &str| graph.nodes.iter().find(|n| n.name == name).unwrap();
+        assert_eq!(node("frontend").role, NodeRole::Root);
+        assert_eq!(node("frontend").source, NodeSource::Local);
+        assert_eq!(node("postgres").role, NodeRole::Dependency);
+        assert_eq!(node("postgres").source, NodeSource::Image);
+        assert_eq!(node("metrics").role, NodeRole::Excluded);
+
+        assert!(graph.edges.contains(&GraphEdge {
+            from: "frontend".to_string(),
+            to: "backend".to_string(),
+            excluded: false,
+        }));
+        assert!(graph.edges.contains(&GraphEdge {
+            from: "frontend".to_string(),
+            to: "metrics".to_string(),
+            excluded: true,
+        }));
+        assert!(graph.edges.contains(&GraphEdge {
+            from: "metrics".to_string(),
+            to: "postgres".to_string(),
+            excluded: true,
+        }));
+    }
+
+    #[test]
+    fn test_render_graph_formats() {
+        let graph = example_graph();
+
+        let dot = render_graph(&graph, GraphFormat::Dot);
+        assert!(dot.starts_with("digraph \"full\" {"));
+        assert!(dot.contains("\"frontend\" [shape=box, style=bold, penwidth=2];"));
+        assert!(
+            dot.contains("\"metrics\" [shape=ellipse, style=dashed, color=gray, fontcolor=gray];")
+        );
+        assert!(dot.contains("\"frontend\" -> \"metrics\" [style=dashed, color=gray];"));
+
+        let mermaid = render_graph(&graph, GraphFormat::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains("svc_postgres([\"postgres\"])"));
+        assert!(mermaid.contains("svc_backend[\"backend\"]"));
+        assert!(mermaid.contains("svc_frontend -.-> svc_metrics"));
+        assert!(mermaid.contains("class svc_frontend root"));
+        assert!(mermaid.contains("class svc_metrics excluded"));
+    }
+}
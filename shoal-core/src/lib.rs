@@ -1,27 +1,23 @@
-use crate::{
-    config::loader::ConfigLoader,
-    manager::ShoalManager,
-    traits::{StdFileSystem, StdPathProvider},
-};
-use anyhow::Result;
-
 mod compose;
 mod config;
 mod docker;
+mod error;
+mod graph;
 mod manager;
 mod override_handler;
+mod provenance;
+mod resolver;
 mod stack;
-mod traits;
-mod types;
-
-pub fn create_shoal_manager() -> Result<ShoalManager> {
-    let file_system = StdFileSystem;
-    let path_provider = StdPathProvider;
-    let config_loader = ConfigLoader::new(file_system, path_provider);
+pub mod traits;
+pub mod types;
+mod validator;
 
-    let services = config_loader.load_services()?;
-    let stacks = config_loader.load_stacks()?;
-    let overrides = config_loader.load_overrides()?;
+pub use compose::OutputFormat;
+pub use error::{Result, ShoalError};
+pub use graph::GraphFormat;
+pub use manager::{ShoalManager, ShoalManagerBuilder};
 
-    Ok(ShoalManager::new(services, stacks, overrides))
+/// Creates a `ShoalManager` backed by the real file system, paths and docker.
+pub fn create_shoal_manager() -> Result<ShoalManager> {
+    ShoalManager::builder().build()
 }
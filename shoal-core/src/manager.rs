@@ -1,28 +1,55 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
-    compose::ComposeFileManager,
+    compose::{ComposeFileManager, OutputFormat},
+    config::loader::ConfigLoader,
+    graph::{GraphFormat, render_graph},
     stack::StackManager,
-    traits::{StdCommandExecutor, StdFileSystem, StdPathProvider},
-    types::{service::Service, stack::Stack, stack_override::StackOverride},
+    traits::{
+        CommandExecutor, FileSystem, PathProvider, StdCommandExecutor, StdFileSystem,
+        StdPathProvider,
+    },
+    types::{
+        config_source::ConfigEntry,
+        graph::ServiceGraph,
+        logs::{LogLine, LogOptions},
+        provenance::{Definition, DefinitionKind, StackProvenance},
+        service::Service,
+        stack::Stack,
+        stack_override::StackOverride,
+        stack_status::StackStatus,
+        validation::ValidationReport,
+    },
+    validator::Validator,
 };
 
-use anyhow::Result;
+use crate::error::{Result, ShoalError};
+use anyhow::anyhow;
 
-pub struct ShoalManager {
-    stack_manager: StackManager<StdFileSystem, StdPathProvider>,
+pub struct ShoalManager<FS: FileSystem = StdFileSystem, PP: PathProvider = StdPathProvider> {
+    stack_manager: StackManager<FS, PP>,
 }
 
 impl ShoalManager {
+    pub fn builder() -> ShoalManagerBuilder {
+        ShoalManagerBuilder::new()
+    }
+}
+
+impl<FS: FileSystem, PP: PathProvider> ShoalManager<FS, PP> {
     pub fn new(
         services: HashMap<String, Service>,
         stacks: HashMap<String, Stack>,
         overrides: HashMap<String, StackOverride>,
+        file_system: FS,
+        path_provider: PP,
+        command_executor: Arc<dyn CommandExecutor>,
     ) -> Self {
-        let file_system = StdFileSystem;
-        let path_provider = StdPathProvider;
         let compose_file_manager = ComposeFileManager::new(file_system, path_provider);
-        let command_executor = Arc::new(StdCommandExecutor);
 
         let stack_manager = StackManager::new(
             services,
@@ -36,10 +63,585 @@ impl ShoalManager {
     }
 
     pub fn up(&self, stack_name: impl Into<String>) -> Result<()> {
-        self.stack_manager.up(stack_name)
+        Ok(self.stack_manager.up(stack_name)?)
+    }
+
+    pub fn render(&self, stack_name: impl Into<String>, format: OutputFormat) -> Result<String> {
+        Ok(self.stack_manager.render(stack_name, format)?)
+    }
+
+    pub fn export(
+        &self,
+        stack_name: impl Into<String>,
+        format: OutputFormat,
+        path: &Path,
+    ) -> Result<()> {
+        Ok(self.stack_manager.export(stack_name, format, path)?)
     }
 
     pub fn down(&self, stack_name: impl Into<String>) -> Result<()> {
-        self.stack_manager.down(stack_name)
+        Ok(self.stack_manager.down(stack_name)?)
+    }
+
+    pub fn status(&self, stack_name: impl Into<String>) -> Result<StackStatus> {
+        Ok(self.stack_manager.status(stack_name)?)
+    }
+
+    pub fn graph(&self, stack_name: impl Into<String>) -> Result<ServiceGraph> {
+        Ok(self.stack_manager.graph(stack_name)?)
+    }
+
+    /// Renders the resolved service graph of a stack as Graphviz DOT or Mermaid.
+    pub fn render_graph(
+        &self,
+        stack_name: impl Into<String>,
+        format: GraphFormat,
+    ) -> Result<String> {
+        Ok(render_graph(&self.graph(stack_name)?, format))
+    }
+
+    /// Which file set each field of a stack's services.
+    pub fn provenance(&self, stack_name: impl Into<String>) -> Result<StackProvenance> {
+        Ok(self.stack_manager.provenance(stack_name)?)
+    }
+
+    /// Every loaded stack, service and override with the files defining it.
+    pub fn definitions(&self) -> Vec<Definition> {
+        let stacks = self
+            .stack_manager
+            .stacks()
+            .values()
+            .map(|stack| Definition {
+                kind: DefinitionKind::Stack,
+                name: stack.name.clone(),
+                active: stack.origin.clone(),
+                shadowed: stack.shadowed.clone(),
+            });
+        let services = self
+            .stack_manager
+            .services()
+            .values()
+            .map(|service| Definition {
+                kind: DefinitionKind::Service,
+                name: service.service_name.clone(),
+                active: service.origin.clone(),
+                shadowed: service.shadowed.clone(),
+            });
+        let overrides = self
+            .stack_manager
+            .overrides()
+            .values()
+            .map(|stack_override| Definition {
+                kind: DefinitionKind::Override,
+                name: stack_override.label(),
+                active: stack_override.origin.clone(),
+                shadowed: stack_override.shadowed.clone(),
+            });
+
+        let mut definitions: Vec<Definition> = stacks.chain(services).chain(overrides).collect();
+        definitions.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        definitions
+    }
+
+    /// Definitions matching `name`. Overrides match on their own name or `stack.override`.
+    pub fn which(&self, name: &str) -> Vec<Definition> {
+        let overrides_named: Vec<String> = self
+            .stack_manager
+            .overrides()
+            .values()
+            .filter(|stack_override| stack_override.name == name)
+            .map(|stack_override| stack_override.label())
+            .collect();
+
+        self.definitions()
+            .into_iter()
+            .filter(|definition| {
+                definition.name == name
+                    || (definition.kind == DefinitionKind::Override
+                        && overrides_named.contains(&definition.name))
+            })
+            .collect()
+    }
+
+    /// Streams the logs of a running stack through `on_line`.
+    pub fn logs(
+        &self,
+        stack_name: impl Into<String>,
+        options: &LogOptions,
+        on_line: &mut dyn FnMut(LogLine),
+    ) -> Result<()> {
+        Ok(self.stack_manager.logs(stack_name, options, on_line)?)
+    }
+
+    pub fn restart(&self, stack_name: impl Into<String>, service: &str) -> Result<()> {
+        Ok(self.stack_manager.restart(stack_name, service)?)
+    }
+
+    /// Rebuilds a Local-source service and recreates its container.
+    pub fn rebuild(
+        &self,
+        stack_name: impl Into<String>,
+        service: &str,
+        with_dependents: bool,
+    ) -> Result<()> {
+        Ok(self
+            .stack_manager
+            .rebuild(stack_name, service, with_dependents)?)
+    }
+
+    /// Runs a command inside one of a running stack's services.
+    pub fn exec(
+        &self,
+        stack_name: impl Into<String>,
+        service: &str,
+        command: &[String],
+    ) -> Result<()> {
+        Ok(self.stack_manager.exec(stack_name, service, command)?)
+    }
+
+    /// Opens an interactive shell in a service, trying bash, then sh, then ash.
+    pub fn shell(&self, stack_name: impl Into<String>, service: &str) -> Result<()> {
+        Ok(self.stack_manager.shell(stack_name, service)?)
+    }
+
+    /// The services a stack runs once dependencies and excludes are resolved.
+    pub fn stack_services(&self, stack_name: &str) -> Result<Vec<String>> {
+        Ok(self.stack_manager.stack_services(stack_name)?)
+    }
+
+    /// Reports the status of every known stack, ordered by name.
+    pub fn status_all(&self) -> Result<Vec<StackStatus>> {
+        let mut stack_names: Vec<&String> = self.stack_manager.stacks().keys().collect();
+        stack_names.sort();
+        stack_names
+            .into_iter()
+            .map(|stack_name| self.status(stack_name.as_str()))
+            .collect()
+    }
+
+    pub fn list_stacks(&self) -> Vec<ConfigEntry> {
+        sorted_entries(
+            self.stack_manager
+                .stacks()
+                .values()
+                .map(|stack| ConfigEntry {
+                    name: stack.name.clone(),
+                    description: stack.description.clone(),
+                    source: stack.origin.clone(),
+                }),
+        )
+    }
+
+    pub fn list_services(&self) -> Vec<ConfigEntry> {
+        sorted_entries(
+            self.stack_manager
+                .services()
+                .values()
+                .map(|service| ConfigEntry {
+                    name: service.service_name.clone(),
+                    description: service.description.clone(),
+                    source: service.origin.clone(),
+                }),
+        )
+    }
+
+    /// Lists overrides by the stack they target, under `*` for any stack.
+    pub fn list_overrides(&self) -> BTreeMap<String, Vec<ConfigEntry>> {
+        let mut grouped: BTreeMap<String, Vec<ConfigEntry>> = BTreeMap::new();
+        for stack_override in self.stack_manager.overrides().values() {
+            grouped
+                .entry(
+                    stack_override
+                        .stack
+                        .clone()
+                        .unwrap_or_else(|| "*".to_string()),
+                )
+                .or_default()
+                .push(ConfigEntry {
+                    name: stack_override.name.clone(),
+                    description: stack_override.description.clone(),
+                    source: stack_override.origin.clone(),
+                });
+        }
+
+        grouped
+            .into_iter()
+            .map(|(stack, entries)| (stack, sorted_entries(entries)))
+            .collect()
+    }
+}
+
+fn sorted_entries(entries: impl IntoIterator<Item = ConfigEntry>) -> Vec<ConfigEntry> {
+    let mut entries: Vec<ConfigEntry> = entries.into_iter().collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    entries
+}
+
+/// Builds a `ShoalManager`, defaulting to the std file system and path provider.
+pub struct ShoalManagerBuilder<FS: FileSystem = StdFileSystem, PP: PathProvider = StdPathProvider> {
+    file_system: FS,
+    path_provider: PP,
+    command_executor: Arc<dyn CommandExecutor>,
+    local_sources: Vec<(String, PathBuf)>,
+}
+
+impl ShoalManagerBuilder {
+    pub fn new() -> Self {
+        Self {
+            file_system: StdFileSystem,
+            path_provider: StdPathProvider,
+            command_executor: Arc::new(StdCommandExecutor),
+            local_sources: Vec::new(),
+        }
+    }
+}
+
+impl Default for ShoalManagerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<FS: FileSystem, PP: PathProvider> ShoalManagerBuilder<FS, PP> {
+    pub fn file_system<F: FileSystem>(self, file_system: F) -> ShoalManagerBuilder<F, PP> {
+        ShoalManagerBuilder {
+            file_system,
+            path_provider: self.path_provider,
+            command_executor: self.command_executor,
+            local_sources: self.local_sources,
+        }
+    }
+
+    pub fn path_provider<P: PathProvider>(self, path_provider: P) -> ShoalManagerBuilder<FS, P> {
+        ShoalManagerBuilder {
+            file_system: self.file_system,
+            path_provider,
+            command_executor: self.command_executor,
+            local_sources: self.local_sources,
+        }
+    }
+
+    pub fn command_executor(mut self, command_executor: Arc<dyn CommandExecutor>) -> Self {
+        self.command_executor = command_executor;
+        self
+    }
+
+    /// Builds `service` from a local checkout at `path`, whatever its configured source.
+    pub fn local_source(mut self, service: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.local_sources.push((service.into(), path.into()));
+        self
+    }
+}
+
+impl<FS: FileSystem + Clone, PP: PathProvider + Clone> ShoalManagerBuilder<FS, PP> {
+    pub fn build(self) -> Result<ShoalManager<FS, PP>> {
+        let config_loader = ConfigLoader::new(self.file_system.clone(), self.path_provider.clone());
+
+        let services = config_loader.load_services()?;
+        let stacks = config_loader.load_stacks()?;
+        let overrides = config_loader.load_overrides()?;
+
+        let mut local_sources = HashMap::new();
+        for (service, path) in self.local_sources {
+            if !services.contains_key(&service) {
+                return Err(ShoalError::MissingService {
+                    referenced_by: "Local source".to_string(),
+                    services: vec![service],
+                });
+            }
+            let path = self.path_provider.current_dir()?.join(path);
+            if !self.file_system.exists(&path) {
+                return Err(anyhow!(
+                    "Local source path {} for '{service}' does not exist",
+                    path.display()
+                )
+                .into());
+            }
+            local_sources.insert(service, path);
+        }
+
+        let mut manager = ShoalManager::new(
+            services,
+            stacks,
+            overrides,
+            self.file_system,
+            self.path_provider,
+            self.command_executor,
+        );
+        manager.stack_manager.set_local_sources(local_sources);
+        Ok(manager)
+    }
+
+    /// Checks every stack, service and override file instead of building a manager.
+    pub fn validate(self) -> Result<ValidationReport> {
+        let validator = Validator::new(self.file_system, self.path_provider, self.command_executor);
+        Ok(validator.validate()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::mocks::{MockCommandExecutor, MockFileSystem, MockPathProvider};
+    use crate::types::{config_source::FileScope, provenance::FieldLayer};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_builder_uses_injected_implementations() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/current/services",
+            "redis.yml",
+            "service_name: redis\nsource:\n  type: Image\n  location: redis:latest\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "cache.yml",
+            "name: cache\ndescription: Just redis\nservices:\n  - redis\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "verbose.yml",
+            "name: verbose\nstack: cache\ndescription: Verbose\noverrides: {}\n",
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = ShoalManager::builder()
+            .file_system(file_system.clone())
+            .path_provider(MockPathProvider::new())
+            .command_executor(command_executor.clone())
+            .build()
+            .unwrap();
+
+        manager.up("cache.verbose").unwrap();
+
+        let compose_path =
+            PathBuf::from("/test/data/shoal/stacks/cache/docker-compose.generated.yml");
+        let compose = file_system.read_file(&compose_path).unwrap();
+        assert!(compose.contains("cache-redis"));
+
+        let calls = command_executor.calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, "docker");
+        assert!(calls[0].1.contains(&"up".to_string()));
+    }
+
+    #[test]
+    fn test_list_reports_scope_and_path() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/home/.shoal/stacks",
+            "cache.yml",
+            "name: cache\ndescription: Global cache\nservices: []\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "cache.yml",
+            "name: cache\ndescription: Local cache\nservices: []\n",
+        );
+        file_system.add_file(
+            "/test/home/.shoal/overrides",
+            "trace.yml",
+            "name: trace\nstack: cache\ndescription: Trace logs\noverrides: {}\n",
+        );
+
+        let manager = ShoalManager::builder()
+            .file_system(file_system)
+            .path_provider(MockPathProvider::new())
+            .build()
+            .unwrap();
+
+        let stacks = manager.list_stacks();
+        assert_eq!(stacks.len(), 1);
+        assert_eq!(stacks[0].description, "Local cache");
+        let source = stacks[0].source.as_ref().unwrap();
+        assert_eq!(source.scope, FileScope::Local);
+        assert_eq!(source.path, PathBuf::from("/test/current/stacks/cache.yml"));
+
+        let overrides = manager.list_overrides();
+        let trace = &overrides["cache"][0];
+        assert_eq!(trace.name, "trace");
+        assert_eq!(trace.source.as_ref().unwrap().scope, FileScope::Global);
+    }
+
+    #[test]
+    fn test_which_reports_shadowed_definitions() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/home/.shoal/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api:1\n",
+        );
+        file_system.add_file(
+            "/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api:2\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "web.yml",
+            "name: web\ndescription: Web\nservices:\n  - api\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "debug.yml",
+            "name: debug\nstack: web\noverrides: {}\n",
+        );
+
+        let manager = ShoalManager::builder()
+            .file_system(file_system)
+            .path_provider(MockPathProvider::new())
+            .build()
+            .unwrap();
+
+        let api = manager.which("api");
+        assert_eq!(api.len(), 1);
+        assert_eq!(api[0].kind, DefinitionKind::Service);
+        let active = api[0].active.as_ref().unwrap();
+        assert_eq!(active.path, PathBuf::from("/test/current/services/api.yml"));
+        assert_eq!(api[0].shadowed.len(), 1);
+        assert_eq!(api[0].shadowed[0].scope, FileScope::Global);
+
+        assert_eq!(manager.which("debug")[0].name, "web.debug");
+        assert_eq!(manager.which("web.debug").len(), 1);
+        assert!(manager.which("missing").is_empty());
+
+        let kinds: Vec<DefinitionKind> = manager.definitions().iter().map(|d| d.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DefinitionKind::Stack,
+                DefinitionKind::Service,
+                DefinitionKind::Override
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provenance_attributes_fields_to_layers() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api:1\n\
+             internal_ports:\n  - \"8080\"\nenv:\n  LOG: info\n  PORT: \"8080\"\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "web.yml",
+            "name: web\ndescription: Web\nservices:\n  - api\n\
+             overrides:\n  api:\n    env:\n      LOG: debug\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "exposed.yml",
+            "name: exposed\nstack: web\noverrides:\n  api:\n    ports:\n      - \"9000:8080\"\n",
+        );
+
+        let manager = ShoalManager::builder()
+            .file_system(file_system)
+            .path_provider(MockPathProvider::new())
+            .build()
+            .unwrap();
+
+        let provenance = manager.provenance("web.exposed").unwrap();
+        let fields: Vec<(&str, &str, FieldLayer, PathBuf)> = provenance.services["api"]
+            .iter()
+            .map(|origin| {
+                (
+                    origin.field,
+                    origin.value.as_str(),
+                    origin.layer,
+                    origin.source.as_ref().unwrap().path.clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    "image",
+                    "api:1",
+                    FieldLayer::Service,
+                    PathBuf::from("/test/current/services/api.yml")
+                ),
+                (
+                    "env",
+                    "LOG=debug",
+                    FieldLayer::Stack,
+                    PathBuf::from("/test/current/stacks/web.yml")
+                ),
+                (
+                    "env",
+                    "PORT=8080",
+                    FieldLayer::Service,
+                    PathBuf::from("/test/current/services/api.yml")
+                ),
+                (
+                    "port",
+                    "9000:8080",
+                    FieldLayer::Override,
+                    PathBuf::from("/test/current/overrides/exposed.yml")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_overrides_and_local_sources() {
+        let file_system = MockFileSystem::new();
+        for name in ["api", "worker"] {
+            file_system.add_file(
+                "/test/current/services",
+                &format!("{name}.yml"),
+                &format!("service_name: {name}\nsource:\n  type: Image\n  location: {name}:1\n"),
+            );
+        }
+        file_system.add_file(
+            "/test/current/stacks",
+            "web.yml",
+            "name: web\ndescription: Web\nservices:\n  - api\n  - worker\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "dev.yml",
+            "name: dev\nstack: web\noverrides:\n  api:\n    source:\n      type: Local\
+             \n      location: ../checkouts/api\n  worker:\n    source:\n      tag: edge\n",
+        );
+        file_system.add_file("/test/checkouts/worker", "Dockerfile", "");
+
+        let render = |builder: ShoalManagerBuilder<MockFileSystem, MockPathProvider>| {
+            let manager = builder.build().unwrap();
+            let rendered = manager.render("web.dev", OutputFormat::Json).unwrap();
+            serde_json::from_str::<serde_json::Value>(&rendered).unwrap()["services"].clone()
+        };
+        let builder = || {
+            ShoalManager::builder()
+                .file_system(file_system.clone())
+                .path_provider(MockPathProvider::new())
+        };
+
+        let services = render(builder());
+        assert_eq!(
+            services["api"]["build"]["context"],
+            "/test/current/overrides/../checkouts/api"
+        );
+        assert!(services["api"]["image"].is_null());
+        assert_eq!(services["worker"]["image"], "worker:edge");
+
+        let services = render(builder().local_source("worker", "/test/checkouts/worker"));
+        assert_eq!(
+            services["worker"]["build"]["context"],
+            "/test/checkouts/worker"
+        );
+        assert!(services["worker"]["image"].is_null());
+
+        let result = builder()
+            .local_source("ghost", "/test/checkouts/worker")
+            .build();
+        assert!(matches!(result, Err(ShoalError::MissingService { .. })));
+        let result = builder().local_source("api", "../missing").build();
+        assert!(result.is_err());
     }
 }
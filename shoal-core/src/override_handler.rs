@@ -1,36 +1,280 @@
 use std::collections::HashMap;
+use std::path::Path;
 
-use crate::types::{docker_service::DockerService, stack::Stack, stack_override::StackOverride};
+use crate::types::{
+    config_source::ConfigSource,
+    docker_service::DockerService,
+    provenance::FieldLayer,
+    service::{LocationType, Service, ServiceLocation},
+    stack::Stack,
+    stack_override::{Override, ReplaceField, SourceOverride, StackOverride},
+};
 
-use tracing::debug;
+use anyhow::{Result, bail};
+use tracing::{debug, trace, warn};
 
-pub fn extract_override(input: &str, stacks: &HashMap<String, Stack>) -> (String, Option<String>) {
+/// Overrides, keyed by service name, with the file they came from.
+pub struct OverrideLayer<'a> {
+    pub layer: FieldLayer,
+    /// The stack or override the layer comes from.
+    pub name: &'a str,
+    pub source: Option<&'a ConfigSource>,
+    pub overrides: HashMap<String, Override>,
+}
+
+/// Groups selectors in key order, then service names, so exact names win.
+pub fn target_services(
+    overrides: &HashMap<String, Override>,
+    services: &[&Service],
+) -> Vec<HashMap<String, Override>> {
+    let mut selectors: Vec<(&String, &Override)> = overrides
+        .iter()
+        .filter(|(key, _)| is_selector(key))
+        .collect();
+    selectors.sort_by_key(|(key, _)| *key);
+
+    let mut groups: Vec<HashMap<String, Override>> = selectors
+        .into_iter()
+        .map(|(selector, service_override)| {
+            let matched: HashMap<String, Override> = services
+                .iter()
+                .filter(|service| matches_selector(selector, service))
+                .map(|service| (service.service_name.clone(), service_override.clone()))
+                .collect();
+            trace!("{selector} matches {:?}", matched.keys());
+            matched
+        })
+        .filter(|matched| !matched.is_empty())
+        .collect();
+
+    let named: HashMap<String, Override> = overrides
+        .iter()
+        .filter(|(key, _)| !is_selector(key))
+        .map(|(key, service_override)| (key.clone(), service_override.clone()))
+        .collect();
+    if !named.is_empty() {
+        groups.push(named);
+    }
+    groups
+}
+
+/// Whether an override key is a wildcard or tag rather than a service name.
+pub fn is_selector(key: &str) -> bool {
+    key.starts_with("tag:") || key.contains('*')
+}
+
+pub fn matches_selector(key: &str, service: &Service) -> bool {
+    match key.strip_prefix("tag:") {
+        Some(tag) => service.tags.iter().any(|t| t == tag),
+        None if key.contains('*') => wildcard_matches(key, &service.service_name),
+        None => key == service.service_name,
+    }
+}
+
+fn wildcard_matches(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(mut rest) = name.strip_prefix(parts[0]) else {
+        return false;
+    };
+    let last = parts[parts.len() - 1];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Splits `stack[.override...]` into the stack name and the overrides chained after it.
+pub fn extract_override(input: &str, stacks: &HashMap<String, Stack>) -> (String, Vec<String>) {
     let parts: Vec<&str> = input.split('.').collect();
 
     for i in (1..=parts.len()).rev() {
         let potential_stack = parts[..i].join(".");
         if stacks.contains_key(&potential_stack) {
-            if i < parts.len() {
-                let override_name = parts[i..].join(".");
-                return (potential_stack, Some(override_name));
-            } else {
-                return (potential_stack, None);
+            let override_names = parts[i..].iter().map(|name| name.to_string()).collect();
+            return (potential_stack, override_names);
+        }
+    }
+
+    (input.to_string(), Vec::new())
+}
+
+/// `(service, field)` pairs that `later` changes from what `earlier` set.
+pub fn override_conflicts(
+    earlier: &HashMap<String, Override>,
+    later: &HashMap<String, Override>,
+) -> Vec<(String, String)> {
+    let mut conflicts = Vec::new();
+    for (service_name, later_override) in later {
+        let Some(earlier_override) = earlier.get(service_name) else {
+            continue;
+        };
+        let mut conflict = |field: String| conflicts.push((service_name.clone(), field));
+
+        if let (Some(earlier_env), Some(later_env)) = (&earlier_override.env, &later_override.env) {
+            for (key, value) in later_env {
+                if earlier_env.get(key).is_some_and(|earlier| earlier != value) {
+                    conflict(format!("env {key}"));
+                }
             }
         }
+
+        if let (Some(earlier_ports), Some(later_ports)) =
+            (&earlier_override.ports, &later_override.ports)
+        {
+            for port in later_ports {
+                let replaces_earlier = earlier_ports.iter().any(|earlier| {
+                    earlier != port && container_port(earlier) == container_port(port)
+                });
+                if replaces_earlier {
+                    conflict(format!("port {}", container_port(port)));
+                }
+            }
+        }
+
+        if let Some(earlier_env) = &earlier_override.env {
+            for key in &later_override.unset_env {
+                if earlier_env.contains_key(key) {
+                    conflict(format!("env {key}"));
+                }
+            }
+        }
+        for port in earlier_override.ports.iter().flatten() {
+            if is_removed(&later_override.remove_ports, port, container_port) {
+                conflict(format!("port {}", container_port(port)));
+            }
+        }
+        for field in &later_override.replace {
+            let replaced = match field {
+                ReplaceField::Env => earlier_override.env.is_some().then_some("env"),
+                ReplaceField::Ports => earlier_override.ports.is_some().then_some("ports"),
+                ReplaceField::Volumes => earlier_override.volumes.is_some().then_some("volumes"),
+            };
+            if let Some(replaced) = replaced {
+                conflict(replaced.to_string());
+            }
+        }
+
+        if earlier_override.command.is_some()
+            && later_override.command.is_some()
+            && earlier_override.command != later_override.command
+        {
+            conflict("command".to_string());
+        }
+        if earlier_override.entrypoint.is_some()
+            && later_override.entrypoint.is_some()
+            && earlier_override.entrypoint != later_override.entrypoint
+        {
+            conflict("entrypoint".to_string());
+        }
     }
 
-    (input.to_string(), None)
+    conflicts.sort();
+    conflicts.dedup();
+    conflicts
+}
+
+/// A copy of `stack` with the services each override adds or removes.
+pub fn apply_service_changes(stack: &Stack, overrides: &[&StackOverride]) -> Stack {
+    let mut stack = stack.clone();
+    for stack_override in overrides {
+        for service in &stack_override.add_services {
+            debug!("Override {} adds service {service}", stack_override.name);
+            stack.exclude.retain(|excluded| excluded != service);
+            if !stack.services.contains(service) {
+                stack.services.push(service.clone());
+            }
+        }
+        for service in &stack_override.remove_services {
+            debug!("Override {} removes service {service}", stack_override.name);
+            stack.services.retain(|included| included != service);
+            if !stack.exclude.contains(service) {
+                stack.exclude.push(service.clone());
+            }
+        }
+    }
+    stack
+}
+
+/// Changes a service's source, resolving a relative location against `base_dir`.
+pub fn apply_source_override(
+    service: &mut Service,
+    source_override: &SourceOverride,
+    base_dir: Option<&Path>,
+) -> Result<()> {
+    let current = service.source.as_ref();
+    let Some(r#type) = source_override.r#type.or(current.map(|s| s.r#type)) else {
+        bail!(
+            "The source override for '{}' needs a type, as the service has no source",
+            service.service_name
+        );
+    };
+    let same_type = current.is_some_and(|s| s.r#type == r#type);
+
+    let location = match (&source_override.location, current) {
+        (Some(location), _) => match (r#type, base_dir) {
+            (LocationType::Local, Some(dir)) if Path::new(location).is_relative() => {
+                dir.join(location).to_string_lossy().to_string()
+            }
+            _ => location.clone(),
+        },
+        (None, Some(current)) if same_type => current.location.clone(),
+        (None, _) => bail!(
+            "The source override for '{}' changes it to {type} but has no location",
+            service.service_name
+        ),
+    };
+
+    let location = match (&source_override.tag, r#type) {
+        (Some(tag), LocationType::Image) => with_tag(&location, tag),
+        (Some(_), LocationType::Local) => {
+            warn!(
+                service = %service.service_name,
+                "Ignoring the tag of a source override for a local source."
+            );
+            location
+        }
+        (None, _) => location,
+    };
+
+    let dockerfile = match &source_override.dockerfile {
+        Some(dockerfile) => Some(dockerfile.clone()),
+        None if same_type => current.and_then(|s| s.dockerfile.clone()),
+        None => None,
+    };
+
+    let source = ServiceLocation {
+        r#type,
+        location,
+        dockerfile,
+    };
+    debug!("  source: {source}");
+    service.source = Some(source);
+    Ok(())
+}
+
+fn with_tag(image: &str, tag: &str) -> String {
+    let name = image.split('@').next().unwrap_or(image);
+    let repository = match name.rfind(':') {
+        Some(i) if !name[i..].contains('/') => &name[..i],
+        _ => name,
+    };
+    format!("{repository}:{tag}")
 }
 
 pub fn apply_overrides(
     docker_services: &mut HashMap<String, DockerService>,
-    override_config: &StackOverride,
+    overrides: &HashMap<String, Override>,
 ) {
     debug!("Applying service overrides");
     for (service_name, service) in docker_services {
-        if let Some(service_override) = override_config.overrides.get(service_name) {
+        if let Some(service_override) = overrides.get(service_name) {
             debug!("Overriding service: {}", service_name);
 
+            apply_replacements(service, service_override);
+            apply_removals(service, service_override);
             apply_env_override(service, service_override);
             apply_ports_override(service, service_override);
             apply_command_override(service, service_override);
@@ -40,26 +284,111 @@ pub fn apply_overrides(
     }
 }
 
-fn apply_env_override(
-    service: &mut DockerService,
-    service_override: &crate::types::stack_override::Override,
+fn apply_replacements(service: &mut DockerService, service_override: &Override) {
+    for field in &service_override.replace {
+        match field {
+            ReplaceField::Env => {
+                debug!("  environment: replacing inherited variables");
+                service.clear_environment();
+            }
+            ReplaceField::Ports => {
+                debug!("  ports: replacing inherited ports");
+                service.ports = None;
+                service.extra.remove("ports");
+            }
+            ReplaceField::Volumes => {
+                debug!("  volumes: replacing inherited volumes");
+                service.volumes = None;
+                service.extra.remove("volumes");
+            }
+        }
+    }
+}
+
+fn apply_removals(service: &mut DockerService, service_override: &Override) {
+    for key in &service_override.unset_env {
+        if service.unset_environment(key) {
+            debug!("  environment: unset {key}");
+        }
+    }
+
+    remove_entries(
+        "ports",
+        &mut service.ports,
+        service.extra.get_mut("ports"),
+        &service_override.remove_ports,
+        container_port,
+    );
+    remove_entries(
+        "volumes",
+        &mut service.volumes,
+        service.extra.get_mut("volumes"),
+        &service_override.remove_volumes,
+        container_path,
+    );
+}
+
+fn remove_entries(
+    key: &str,
+    entries: &mut Option<Vec<String>>,
+    long_syntax: Option<&mut serde_json::Value>,
+    removals: &[String],
+    target: fn(&str) -> &str,
 ) {
+    if removals.is_empty() {
+        return;
+    }
+    debug!("  {key}: removing {removals:?}");
+
+    if let Some(entries) = entries.as_mut() {
+        entries.retain(|entry| !is_removed(removals, entry, target));
+    }
+    if entries.as_ref().is_some_and(Vec::is_empty) {
+        *entries = None;
+    }
+
+    if let Some(serde_json::Value::Array(items)) = long_syntax {
+        items.retain(|item| match item {
+            serde_json::Value::String(entry) => !is_removed(removals, entry, target),
+            serde_json::Value::Object(fields) => {
+                let item_target = match fields.get("target") {
+                    Some(serde_json::Value::String(t)) => t.clone(),
+                    Some(t) => t.to_string(),
+                    None => return true,
+                };
+                !removals.contains(&item_target)
+            }
+            _ => true,
+        });
+    }
+}
+
+fn is_removed(removals: &[String], entry: &str, target: fn(&str) -> &str) -> bool {
+    removals.iter().any(|removal| {
+        if removal.contains(':') {
+            removal == entry
+        } else {
+            removal == target(entry)
+        }
+    })
+}
+
+fn apply_env_override(service: &mut DockerService, service_override: &Override) {
     if let Some(env) = &service_override.env {
-        let service_env = service.environment.clone().unwrap_or_default();
-        let merged_env = merge_hashmaps(&service_env, env);
         debug!("  environment: {} variables set/overridden", env.len());
         for (key, value) in env {
-            debug!("    {}={}", key, value);
+            trace!("    {}={}", key, value);
         }
-        service.environment = Some(merged_env);
+        service.merge_environment(env);
     }
 }
 
-fn apply_ports_override(
-    service: &mut DockerService,
-    service_override: &crate::types::stack_override::Override,
-) {
+fn apply_ports_override(service: &mut DockerService, service_override: &Override) {
     if let Some(ports) = &service_override.ports {
+        if service.append_passthrough("ports", ports) {
+            return;
+        }
+
         let mut service_ports = service
             .ports
             .as_ref()
@@ -70,13 +399,12 @@ fn apply_ports_override(
         for port_str in ports {
             debug!("    {}", port_str);
 
-            let internal_port = if port_str.contains(':') {
-                port_str.split(':').nth(1).unwrap_or(port_str).to_string()
-            } else {
-                port_str.clone()
-            };
+            let internal_port = container_port(port_str);
 
-            if let Some(existing) = service_ports.iter_mut().find(|p| **p == internal_port) {
+            if let Some(existing) = service_ports
+                .iter_mut()
+                .find(|p| container_port(p) == internal_port)
+            {
                 debug!("      (replaced existing port mapping)");
                 *existing = port_str.clone();
             } else {
@@ -88,31 +416,28 @@ fn apply_ports_override(
     }
 }
 
-fn apply_command_override(
-    service: &mut DockerService,
-    service_override: &crate::types::stack_override::Override,
-) {
+fn apply_command_override(service: &mut DockerService, service_override: &Override) {
     if let Some(command) = &service_override.command {
         debug!("  command: {:?}", command);
+        service.extra.remove("command");
         service.command = Some(command.clone());
     }
 }
 
-fn apply_entrypoint_override(
-    service: &mut DockerService,
-    service_override: &crate::types::stack_override::Override,
-) {
+fn apply_entrypoint_override(service: &mut DockerService, service_override: &Override) {
     if let Some(entrypoint) = &service_override.entrypoint {
         debug!("  entrypoint: {:?}", entrypoint);
+        service.extra.remove("entrypoint");
         service.entrypoint = Some(entrypoint.clone());
     }
 }
 
-fn apply_volumes_override(
-    service: &mut DockerService,
-    service_override: &crate::types::stack_override::Override,
-) {
+fn apply_volumes_override(service: &mut DockerService, service_override: &Override) {
     if let Some(volumes) = &service_override.volumes {
+        if service.append_passthrough("volumes", volumes) {
+            return;
+        }
+
         let mut service_volumes = service
             .volumes
             .as_ref()
@@ -129,19 +454,31 @@ fn apply_volumes_override(
     }
 }
 
-fn merge_hashmaps(
-    a: &HashMap<String, String>,
-    b: &HashMap<String, String>,
-) -> HashMap<String, String> {
-    let mut result = a.clone();
-    result.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
-    result
+fn container_port(port: &str) -> &str {
+    let container = port.rsplit(':').next().unwrap_or(port);
+    container.split('/').next().unwrap_or(container)
+}
+
+fn container_path(volume: &str) -> &str {
+    volume.split(':').nth(1).unwrap_or(volume)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::types::stack::Stack;
+    use std::collections::BTreeMap;
+
+    fn env_override(env: &[(&str, &str)]) -> Override {
+        Override {
+            env: Some(
+                env.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn test_extract_override_exact_match() {
@@ -152,12 +489,16 @@ mod tests {
                 name: "my-stack".to_string(),
                 description: "Test stack".to_string(),
                 services: vec![],
+                exclude: vec![],
+                overrides: HashMap::new(),
+                origin: None,
+                shadowed: Vec::new(),
             },
         );
 
-        let (stack_name, override_name) = extract_override("my-stack", &stacks);
+        let (stack_name, override_names) = extract_override("my-stack", &stacks);
         assert_eq!(stack_name, "my-stack");
-        assert_eq!(override_name, None);
+        assert!(override_names.is_empty());
     }
 
     #[test]
@@ -169,12 +510,20 @@ mod tests {
                 name: "my-stack".to_string(),
                 description: "Test stack".to_string(),
                 services: vec![],
+                exclude: vec![],
+                overrides: HashMap::new(),
+                origin: None,
+                shadowed: Vec::new(),
             },
         );
 
-        let (stack_name, override_name) = extract_override("my-stack.dev", &stacks);
+        let (stack_name, override_names) = extract_override("my-stack.dev", &stacks);
+        assert_eq!(stack_name, "my-stack");
+        assert_eq!(override_names, vec!["dev"]);
+
+        let (stack_name, override_names) = extract_override("my-stack.dev.debugger", &stacks);
         assert_eq!(stack_name, "my-stack");
-        assert_eq!(override_name, Some("dev".to_string()));
+        assert_eq!(override_names, vec!["dev", "debugger"]);
     }
 
     #[test]
@@ -186,6 +535,10 @@ mod tests {
                 name: "my".to_string(),
                 description: "Test stack".to_string(),
                 services: vec![],
+                exclude: vec![],
+                overrides: HashMap::new(),
+                origin: None,
+                shadowed: Vec::new(),
             },
         );
         stacks.insert(
@@ -194,29 +547,450 @@ mod tests {
                 name: "my.stack".to_string(),
                 description: "Test stack".to_string(),
                 services: vec![],
+                exclude: vec![],
+                overrides: HashMap::new(),
+                origin: None,
+                shadowed: Vec::new(),
             },
         );
 
         // Should match the longest stack name
-        let (stack_name, override_name) = extract_override("my.stack.dev", &stacks);
+        let (stack_name, override_names) = extract_override("my.stack.dev", &stacks);
         assert_eq!(stack_name, "my.stack");
-        assert_eq!(override_name, Some("dev".to_string()));
+        assert_eq!(override_names, vec!["dev"]);
+    }
+
+    #[test]
+    fn test_named_overrides_layer_on_stack_overrides() {
+        let mut docker_services = HashMap::new();
+        docker_services.insert("backend".to_string(), DockerService::default());
+
+        let stack_overrides = HashMap::from([(
+            "backend".to_string(),
+            env_override(&[("LoggingLevel", "Info"), ("Db", "localhost")]),
+        )]);
+        let named_overrides = HashMap::from([(
+            "backend".to_string(),
+            env_override(&[("LoggingLevel", "Trace")]),
+        )]);
+
+        apply_overrides(&mut docker_services, &stack_overrides);
+        apply_overrides(&mut docker_services, &named_overrides);
+
+        let env = docker_services["backend"].environment.as_ref().unwrap();
+        assert_eq!(env.get("LoggingLevel"), Some(&"Trace".to_string()));
+        assert_eq!(env.get("Db"), Some(&"localhost".to_string()));
+    }
+
+    #[test]
+    fn test_override_conflicts() {
+        let mut earlier = env_override(&[("LOG", "debug"), ("DB", "localhost")]);
+        earlier.ports = Some(vec!["9229:9229".to_string()]);
+        earlier.command = Some(vec![
+            "npm".to_string(),
+            "run".to_string(),
+            "dev".to_string(),
+        ]);
+
+        let mut later = env_override(&[("LOG", "trace"), ("DB", "localhost"), ("NEW", "1")]);
+        later.ports = Some(vec!["9230:9229".to_string(), "3000".to_string()]);
+        later.volumes = Some(vec!["./src:/src".to_string()]);
+
+        let conflicts = override_conflicts(
+            &HashMap::from([("backend".to_string(), earlier)]),
+            &HashMap::from([("backend".to_string(), later)]),
+        );
+
+        assert_eq!(
+            conflicts,
+            vec![
+                ("backend".to_string(), "env LOG".to_string()),
+                ("backend".to_string(), "port 9229".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removals_drop_inherited_values() {
+        let mut service = DockerService {
+            environment: Some(HashMap::from([
+                ("LOG".to_string(), "info".to_string()),
+                ("DEBUG".to_string(), "1".to_string()),
+            ])),
+            ports: Some(vec!["5432:5432".to_string(), "8080".to_string()]),
+            volumes: Some(vec![
+                "/home/dev/data:/data".to_string(),
+                "/home/dev/src:/src:ro".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let service_override = Override {
+            ports: Some(vec!["9229:9229".to_string()]),
+            unset_env: vec!["DEBUG".to_string(), "MISSING".to_string()],
+            remove_ports: vec!["5432".to_string(), "8080:8080".to_string()],
+            remove_volumes: vec!["/src".to_string()],
+            ..Default::default()
+        };
+
+        let mut docker_services = HashMap::from([("db".to_string(), service)]);
+        apply_overrides(
+            &mut docker_services,
+            &HashMap::from([("db".to_string(), service_override)]),
+        );
+        service = docker_services.remove("db").unwrap();
+
+        let env = service.environment.unwrap();
+        assert_eq!(env.len(), 1);
+        assert_eq!(env["LOG"], "info");
+        // `8080:8080` has to match the whole entry, so the bare `8080` stays.
+        assert_eq!(
+            service.ports,
+            Some(vec!["8080".to_string(), "9229:9229".to_string()])
+        );
+        assert_eq!(
+            service.volumes,
+            Some(vec!["/home/dev/data:/data".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_replace_drops_inherited_fields() {
+        let mut service = DockerService {
+            environment: Some(HashMap::from([("LOG".to_string(), "info".to_string())])),
+            volumes: Some(vec!["data:/data".to_string()]),
+            ..Default::default()
+        };
+        service.extra.insert(
+            "ports".to_string(),
+            serde_json::json!([{ "target": 80, "published": 8080 }]),
+        );
+
+        let mut service_override = env_override(&[("MODE", "test")]);
+        service_override.ports = Some(vec!["3000:80".to_string()]);
+        service_override.replace = vec![ReplaceField::Env, ReplaceField::Ports];
+        apply_replacements(&mut service, &service_override);
+        apply_env_override(&mut service, &service_override);
+        apply_ports_override(&mut service, &service_override);
+
+        assert_eq!(
+            service.environment,
+            Some(HashMap::from([("MODE".to_string(), "test".to_string())]))
+        );
+        assert!(!service.extra.contains_key("ports"));
+        assert_eq!(service.ports, Some(vec!["3000:80".to_string()]));
+        assert_eq!(service.volumes, Some(vec!["data:/data".to_string()]));
+    }
+
+    #[test]
+    fn test_removals_match_long_syntax_targets() {
+        let mut service = DockerService::default();
+        service.extra.insert(
+            "ports".to_string(),
+            serde_json::json!([{ "target": 80, "published": 8080 }, "9229:9229"]),
+        );
+        service.extra.insert(
+            "volumes".to_string(),
+            serde_json::json!([{ "type": "bind", "source": "./src", "target": "/src" }]),
+        );
+
+        let service_override = Override {
+            remove_ports: vec!["80".to_string(), "9229:9229".to_string()],
+            remove_volumes: vec!["/src".to_string()],
+            ..Default::default()
+        };
+        apply_removals(&mut service, &service_override);
+
+        assert_eq!(service.extra["ports"], serde_json::json!([]));
+        assert_eq!(service.extra["volumes"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_env_removals_cover_compose_environment() {
+        let mut listed = DockerService::default();
+        listed.extra.insert(
+            "environment".to_string(),
+            serde_json::json!(["FOO", "BAR=1", "KEEP=1"]),
+        );
+        let mut mapped = DockerService::default();
+        mapped.extra.insert(
+            "environment".to_string(),
+            serde_json::json!({ "FOO": null, "KEEP": "1" }),
+        );
+
+        let unset = Override {
+            unset_env: vec!["FOO".to_string(), "BAR".to_string()],
+            ..Default::default()
+        };
+        apply_removals(&mut listed, &unset);
+        apply_removals(&mut mapped, &unset);
+
+        assert_eq!(listed.extra["environment"], serde_json::json!(["KEEP=1"]));
+        assert_eq!(
+            mapped.extra["environment"],
+            serde_json::json!({ "KEEP": "1" })
+        );
+
+        let mut replace = env_override(&[("MODE", "test")]);
+        replace.replace = vec![ReplaceField::Env];
+        apply_replacements(&mut listed, &replace);
+        apply_env_override(&mut listed, &replace);
+
+        assert!(!listed.extra.contains_key("environment"));
+        assert_eq!(
+            listed.environment,
+            Some(HashMap::from([("MODE".to_string(), "test".to_string())]))
+        );
+    }
+
+    #[test]
+    fn test_later_port_mapping_replaces_earlier_one() {
+        let mut docker_services = HashMap::from([(
+            "api".to_string(),
+            DockerService {
+                ports: Some(vec!["8080".to_string(), "9229".to_string()]),
+                ..Default::default()
+            },
+        )]);
+        for port in ["9000:8080", "9001:8080"] {
+            let service_override = Override {
+                ports: Some(vec![port.to_string()]),
+                ..Default::default()
+            };
+            apply_overrides(
+                &mut docker_services,
+                &HashMap::from([("api".to_string(), service_override)]),
+            );
+        }
+
+        assert_eq!(
+            docker_services["api"].ports,
+            Some(vec!["9001:8080".to_string(), "9229".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_port_removals_match_host_ip_mappings() {
+        assert_eq!(container_port("127.0.0.1:8080:80"), "80");
+        assert_eq!(container_port("8080:80/udp"), "80");
+        assert_eq!(container_port("9229"), "9229");
+
+        let mut service = DockerService {
+            ports: Some(vec![
+                "127.0.0.1:8080:80".to_string(),
+                "127.0.0.1:9229:9229/tcp".to_string(),
+                "5432".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let service_override = Override {
+            remove_ports: vec!["80".to_string(), "8080".to_string()],
+            ..Default::default()
+        };
+        apply_removals(&mut service, &service_override);
+
+        assert_eq!(
+            service.ports,
+            Some(vec![
+                "127.0.0.1:9229:9229/tcp".to_string(),
+                "5432".to_string()
+            ])
+        );
+    }
+
+    fn image_service(image: &str) -> Service {
+        Service {
+            service_name: "backend".to_string(),
+            source: Some(ServiceLocation {
+                r#type: LocationType::Image,
+                location: image.to_string(),
+                dockerfile: None,
+            }),
+            ..Default::default()
+        }
     }
 
     #[test]
-    fn test_merge_hashmaps() {
-        let mut a = HashMap::new();
-        a.insert("KEY1".to_string(), "value1".to_string());
-        a.insert("KEY2".to_string(), "value2".to_string());
+    fn test_source_override_swaps_image_for_local_path() {
+        let mut service = image_service("ghcr.io/acme/backend:1.4");
+        let source_override = SourceOverride {
+            r#type: Some(LocationType::Local),
+            location: Some("../backend".to_string()),
+            dockerfile: Some("Dockerfile.dev".to_string()),
+            ..Default::default()
+        };
 
-        let mut b = HashMap::new();
-        b.insert("KEY2".to_string(), "new_value2".to_string());
-        b.insert("KEY3".to_string(), "value3".to_string());
+        apply_source_override(
+            &mut service,
+            &source_override,
+            Some(Path::new("/home/dev/.shoal/overrides")),
+        )
+        .unwrap();
+
+        let source = service.source.unwrap();
+        assert_eq!(source.r#type, LocationType::Local);
+        assert_eq!(source.location, "/home/dev/.shoal/overrides/../backend");
+        assert_eq!(source.dockerfile.as_deref(), Some("Dockerfile.dev"));
+    }
+
+    #[test]
+    fn test_source_override_changes_image_tag() {
+        let mut service = image_service("localhost:5000/backend:1.4");
+        let source_override = SourceOverride {
+            tag: Some("pr-123".to_string()),
+            ..Default::default()
+        };
+        apply_source_override(&mut service, &source_override, None).unwrap();
+        assert_eq!(
+            service.source.unwrap().location,
+            "localhost:5000/backend:pr-123"
+        );
+
+        assert_eq!(with_tag("postgres", "16"), "postgres:16");
+        assert_eq!(
+            with_tag("localhost:5000/app", "dev"),
+            "localhost:5000/app:dev"
+        );
+        assert_eq!(with_tag("app:1@sha256:abc", "2"), "app:2");
+    }
+
+    #[test]
+    fn test_source_override_needs_location_to_change_type() {
+        let mut service = image_service("backend:1.4");
+        let source_override = SourceOverride {
+            r#type: Some(LocationType::Local),
+            ..Default::default()
+        };
+
+        let error = apply_source_override(&mut service, &source_override, None).unwrap_err();
+        assert!(error.to_string().contains("has no location"));
+        assert_eq!(service.source.unwrap().location, "backend:1.4");
+    }
 
-        let merged = merge_hashmaps(&a, &b);
-        assert_eq!(merged.get("KEY1"), Some(&"value1".to_string()));
-        assert_eq!(merged.get("KEY2"), Some(&"new_value2".to_string())); // b overrides a
-        assert_eq!(merged.get("KEY3"), Some(&"value3".to_string()));
-        assert_eq!(merged.len(), 3);
+    #[test]
+    fn test_ports_override_appends_to_long_syntax() {
+        let mut service = DockerService::default();
+        service.extra.insert(
+            "ports".to_string(),
+            serde_json::json!([{ "target": 80, "published": 8080 }]),
+        );
+
+        let mut override_config = env_override(&[]);
+        override_config.env = None;
+        override_config.ports = Some(vec!["9229:9229".to_string()]);
+        apply_ports_override(&mut service, &override_config);
+
+        assert!(service.ports.is_none());
+        assert_eq!(service.extra["ports"][1], "9229:9229");
+    }
+
+    #[test]
+    fn test_command_override_replaces_compose_command() {
+        let mut service = DockerService::default();
+        service
+            .extra
+            .insert("command".to_string(), serde_json::json!("npm start"));
+
+        let mut override_config = env_override(&[]);
+        override_config.env = None;
+        override_config.command = Some(vec!["npm".to_string(), "test".to_string()]);
+        apply_command_override(&mut service, &override_config);
+
+        assert!(!service.extra.contains_key("command"));
+        assert_eq!(
+            service.command,
+            Some(vec!["npm".to_string(), "test".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_target_services_resolves_selectors() {
+        let service = |name: &str, tags: &[&str]| Service {
+            service_name: name.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let (api, worker, postgres) = (
+            service("shoal-api", &["aws"]),
+            service("shoal-worker", &["aws"]),
+            service("postgres", &[]),
+        );
+        let overrides = HashMap::from([
+            ("shoal-*".to_string(), env_override(&[("LOG", "debug")])),
+            ("tag:aws".to_string(), env_override(&[("AWS", "1")])),
+            ("postgres".to_string(), env_override(&[("LOG", "warn")])),
+            ("tag:gcp".to_string(), env_override(&[("GCP", "1")])),
+        ]);
+
+        let groups = target_services(&overrides, &[&api, &worker, &postgres]);
+
+        let targets: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|group| {
+                let mut names: Vec<&str> = group.keys().map(String::as_str).collect();
+                names.sort();
+                names
+            })
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                vec!["shoal-api", "shoal-worker"],
+                vec!["shoal-api", "shoal-worker"],
+                vec!["postgres"],
+            ]
+        );
+        assert!(
+            groups[1]["shoal-api"]
+                .env
+                .as_ref()
+                .unwrap()
+                .contains_key("AWS")
+        );
+        assert!(wildcard_matches("*-api", "shoal-api"));
+        assert!(wildcard_matches("s*l*i", "shoal-api"));
+        assert!(!wildcard_matches("*-worker*x", "shoal-worker"));
+    }
+
+    #[test]
+    fn test_env_override_merges_with_existing_environment() {
+        let mut services = HashMap::from([
+            (
+                "typed".to_string(),
+                DockerService {
+                    environment: Some(HashMap::from([
+                        ("KEY1".to_string(), "value1".to_string()),
+                        ("KEY2".to_string(), "value2".to_string()),
+                    ])),
+                    ..Default::default()
+                },
+            ),
+            (
+                "passthrough".to_string(),
+                DockerService {
+                    extra: BTreeMap::from([(
+                        "environment".to_string(),
+                        serde_json::json!(["FOO", "KEY2=value2"]),
+                    )]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let service_override = env_override(&[("KEY2", "new_value2"), ("KEY3", "value3")]);
+        let overrides = HashMap::from([
+            ("typed".to_string(), service_override.clone()),
+            ("passthrough".to_string(), service_override),
+        ]);
+
+        apply_overrides(&mut services, &overrides);
+
+        let environment = services["typed"].environment.as_ref().unwrap();
+        assert_eq!(environment["KEY1"], "value1");
+        assert_eq!(environment["KEY2"], "new_value2");
+        assert_eq!(environment["KEY3"], "value3");
+        assert!(services["passthrough"].environment.is_none());
+        assert_eq!(
+            services["passthrough"].extra["environment"],
+            serde_json::json!(["FOO", "KEY2=new_value2", "KEY3=value3"])
+        );
     }
 }
@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::override_handler::{OverrideLayer, apply_overrides};
+use crate::types::{
+    config_source::ConfigSource,
+    docker_service::DockerService,
+    provenance::{FieldLayer, FieldOrigin},
+    service::Service,
+};
+
+/// Attributes each field of the generated services to the layer that set it.
+pub fn attribute_fields(
+    base: &HashMap<String, DockerService>,
+    services: &HashMap<String, Service>,
+    layers: &[OverrideLayer],
+) -> BTreeMap<String, Vec<FieldOrigin>> {
+    let mut docker_services = base.clone();
+    let mut origins: HashMap<String, Vec<FieldOrigin>> = base
+        .iter()
+        .map(|(name, docker_service)| {
+            let source = services.get(name).and_then(|s| s.origin.as_ref());
+            let fields = service_fields(docker_service)
+                .into_iter()
+                .map(|(field, value)| field_origin(field, value, FieldLayer::Service, source))
+                .collect();
+            (name.clone(), fields)
+        })
+        .collect();
+
+    for layer in layers {
+        apply_overrides(&mut docker_services, &layer.overrides);
+
+        for (name, service_override) in &layer.overrides {
+            let (Some(docker_service), Some(previous)) =
+                (docker_services.get(name), origins.get_mut(name))
+            else {
+                continue;
+            };
+            let mut fields: Vec<FieldOrigin> = service_fields(docker_service)
+                .into_iter()
+                .map(|(field, value)| {
+                    match previous
+                        .iter()
+                        .position(|p| p.field == field && p.value == value)
+                    {
+                        Some(index) => previous.remove(index),
+                        None => field_origin(field, value, layer.layer, layer.source),
+                    }
+                })
+                .collect();
+
+            // Source changes are applied before the base service is built; only who made
+            // them is new.
+            if service_override.source.is_some()
+                && let Some(current) = fields.iter_mut().find(|f| is_source(f.field))
+            {
+                *current = field_origin(
+                    current.field,
+                    current.value.clone(),
+                    layer.layer,
+                    layer.source,
+                );
+            }
+            *previous = fields;
+        }
+    }
+
+    origins.into_iter().collect()
+}
+
+fn is_source(field: &str) -> bool {
+    field == "image" || field == "build"
+}
+
+fn service_fields(docker_service: &DockerService) -> Vec<(&'static str, String)> {
+    let mut fields = Vec::new();
+
+    match (&docker_service.build, &docker_service.image) {
+        (Some(build), _) => fields.push(("build", build.context.clone())),
+        (None, Some(image)) => fields.push(("image", image.clone())),
+        (None, None) => {}
+    }
+
+    let mut env: Vec<String> = docker_service
+        .environment
+        .iter()
+        .flatten()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    match docker_service.extra.get("environment") {
+        Some(Value::Object(variables)) => {
+            env.extend(variables.iter().map(|(key, value)| match value {
+                Value::Null => key.clone(),
+                value => format!("{key}={}", display_value(value)),
+            }))
+        }
+        Some(Value::Array(entries)) => env.extend(entries.iter().map(display_value)),
+        _ => {}
+    }
+    env.sort();
+    fields.extend(env.into_iter().map(|entry| ("env", entry)));
+
+    for (field, key, typed) in [
+        ("port", "ports", &docker_service.ports),
+        ("volume", "volumes", &docker_service.volumes),
+    ] {
+        fields.extend(typed.iter().flatten().map(|entry| (field, entry.clone())));
+        if let Some(Value::Array(entries)) = docker_service.extra.get(key) {
+            fields.extend(entries.iter().map(|entry| (field, display_value(entry))));
+        }
+    }
+
+    for (field, typed) in [
+        ("command", &docker_service.command),
+        ("entrypoint", &docker_service.entrypoint),
+    ] {
+        let value = typed
+            .as_ref()
+            .map(|parts| parts.join(" "))
+            .or_else(|| docker_service.extra.get(field).map(display_value));
+        fields.extend(value.map(|value| (field, value)));
+    }
+
+    fields
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(" "),
+        value => value.to_string(),
+    }
+}
+
+fn field_origin(
+    field: &'static str,
+    value: String,
+    layer: FieldLayer,
+    source: Option<&ConfigSource>,
+) -> FieldOrigin {
+    FieldOrigin {
+        field,
+        value,
+        layer,
+        source: source.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::config_source::FileScope;
+    use crate::types::stack_override::Override;
+    use std::path::PathBuf;
+
+    fn source(path: &str) -> ConfigSource {
+        ConfigSource {
+            scope: FileScope::Local,
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn test_later_layers_take_over_fields() {
+        let base = HashMap::from([(
+            "api".to_string(),
+            DockerService {
+                command: Some(vec!["serve".to_string()]),
+                volumes: Some(vec!["data:/data".to_string()]),
+                ..Default::default()
+            },
+        )]);
+        let overrides = HashMap::from([(
+            "api".to_string(),
+            Override {
+                command: Some(vec!["serve".to_string(), "--debug".to_string()]),
+                volumes: Some(vec!["./src:/src".to_string()]),
+                ..Default::default()
+            },
+        )]);
+        let override_source = source("/overrides/debug.yml");
+
+        let fields = attribute_fields(
+            &base,
+            &HashMap::new(),
+            &[OverrideLayer {
+                layer: FieldLayer::Override,
+                name: "debug",
+                source: Some(&override_source),
+                overrides,
+            }],
+        );
+
+        let summary: Vec<(&str, &str, FieldLayer)> = fields["api"]
+            .iter()
+            .map(|origin| (origin.field, origin.value.as_str(), origin.layer))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("volume", "data:/data", FieldLayer::Service),
+                ("volume", "./src:/src", FieldLayer::Override),
+                ("command", "serve --debug", FieldLayer::Override),
+            ]
+        );
+        assert_eq!(fields["api"][2].source, Some(override_source));
+    }
+
+    #[test]
+    fn test_fields_kept_verbatim_are_attributed() {
+        let base = HashMap::from([(
+            "api".to_string(),
+            DockerService {
+                extra: BTreeMap::from([
+                    (
+                        "environment".to_string(),
+                        serde_json::json!(["FOO", "LOG=info"]),
+                    ),
+                    (
+                        "volumes".to_string(),
+                        serde_json::json!([{ "type": "volume", "source": "data", "target": "/data" }]),
+                    ),
+                ]),
+                ..Default::default()
+            },
+        )]);
+        let overrides = HashMap::from([(
+            "api".to_string(),
+            Override {
+                env: Some(HashMap::from([("LOG".to_string(), "debug".to_string())])),
+                unset_env: vec!["FOO".to_string()],
+                volumes: Some(vec!["./src:/src".to_string()]),
+                ..Default::default()
+            },
+        )]);
+
+        let fields = attribute_fields(
+            &base,
+            &HashMap::new(),
+            &[OverrideLayer {
+                layer: FieldLayer::Override,
+                name: "debug",
+                source: None,
+                overrides,
+            }],
+        );
+
+        let summary: Vec<(&str, &str, FieldLayer)> = fields["api"]
+            .iter()
+            .map(|origin| (origin.field, origin.value.as_str(), origin.layer))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("env", "LOG=debug", FieldLayer::Override),
+                (
+                    "volume",
+                    r#"{"source":"data","target":"/data","type":"volume"}"#,
+                    FieldLayer::Service
+                ),
+                ("volume", "./src:/src", FieldLayer::Override),
+            ]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{error::ShoalError, types::service::Service};
+
+use anyhow::{Result, bail};
+use tracing::{debug, warn};
+
+/// Every service reachable from `roots` outside `exclude`, dependencies first.
+pub fn resolve_dependencies(
+    roots: &[String],
+    exclude: &[String],
+    services: &HashMap<String, Service>,
+) -> Result<Vec<String>> {
+    let mut resolved = Vec::new();
+    let mut visited = HashSet::new();
+    let mut path = Vec::new();
+    let walk = Walk { services, exclude };
+
+    for root in roots {
+        if exclude.contains(root) {
+            debug!("Skipping excluded root service {root}");
+            continue;
+        }
+        walk.visit(root, &mut visited, &mut path, &mut resolved)?;
+    }
+
+    debug!("Resolved services in start order: {:?}", resolved);
+    Ok(resolved)
+}
+
+struct Walk<'a> {
+    services: &'a HashMap<String, Service>,
+    exclude: &'a [String],
+}
+
+impl Walk<'_> {
+    fn visit(
+        &self,
+        service_name: &str,
+        visited: &mut HashSet<String>,
+        path: &mut Vec<String>,
+        resolved: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some(start) = path.iter().position(|name| name == service_name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(service_name.to_string());
+            bail!(ShoalError::DependencyCycle { path: cycle });
+        }
+
+        if visited.contains(service_name) {
+            return Ok(());
+        }
+
+        let Some(service) = self.services.get(service_name) else {
+            let referenced_by = match path.last() {
+                Some(parent) => format!("Service '{parent}'"),
+                None => "Stack".to_string(),
+            };
+            bail!(ShoalError::MissingService {
+                referenced_by,
+                services: vec![service_name.to_string()],
+            });
+        };
+
+        path.push(service_name.to_string());
+        for dependency in &service.dependencies {
+            if self.exclude.contains(dependency) {
+                warn!(
+                    service = %service_name,
+                    dependency = %dependency,
+                    "Excluded service is a dependency of a service still in the stack."
+                );
+                continue;
+            }
+            self.visit(dependency, visited, path, resolved)?;
+        }
+        path.pop();
+
+        visited.insert(service_name.to_string());
+        resolved.push(service_name.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::service::{LocationType, ServiceLocation};
+
+    fn create_test_service(name: &str, dependencies: &[&str]) -> Service {
+        Service {
+            service_name: name.to_string(),
+            source: Some(ServiceLocation {
+                r#type: LocationType::Image,
+                location: "test/image:latest".to_string(),
+                dockerfile: None,
+            }),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            ..Service::default()
+        }
+    }
+
+    fn services(definitions: &[(&str, &[&str])]) -> HashMap<String, Service> {
+        definitions
+            .iter()
+            .map(|(name, deps)| (name.to_string(), create_test_service(name, deps)))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_transitive_dependencies() {
+        let services = services(&[
+            ("frontend", &["backend"]),
+            ("backend", &["postgres", "redis"]),
+            ("postgres", &[]),
+            ("redis", &[]),
+        ]);
+
+        let resolved = resolve_dependencies(&["frontend".to_string()], &[], &services).unwrap();
+        assert_eq!(resolved, vec!["postgres", "redis", "backend", "frontend"]);
+    }
+
+    #[test]
+    fn test_resolve_shared_dependency_once() {
+        let services = services(&[
+            ("backend-1", &["postgres"]),
+            ("backend-2", &["postgres"]),
+            ("postgres", &[]),
+        ]);
+
+        let roots = vec!["backend-1".to_string(), "backend-2".to_string()];
+        let resolved = resolve_dependencies(&roots, &[], &services).unwrap();
+        assert_eq!(resolved, vec!["postgres", "backend-1", "backend-2"]);
+    }
+
+    #[test]
+    fn test_resolve_exclude_prunes_subtree() {
+        let services = services(&[
+            ("frontend", &["backend-1", "backend-2"]),
+            ("backend-1", &["postgres"]),
+            ("backend-2", &["redis", "postgres"]),
+            ("postgres", &[]),
+            ("redis", &[]),
+        ]);
+
+        let resolved = resolve_dependencies(
+            &["frontend".to_string()],
+            &["backend-2".to_string()],
+            &services,
+        )
+        .unwrap();
+        assert_eq!(resolved, vec!["postgres", "backend-1", "frontend"]);
+    }
+
+    #[test]
+    fn test_resolve_exclude_keeps_shared_dependency() {
+        let services = services(&[
+            ("backend-1", &["postgres"]),
+            ("backend-2", &["postgres"]),
+            ("postgres", &[]),
+        ]);
+
+        let roots = vec!["backend-1".to_string(), "backend-2".to_string()];
+        let resolved = resolve_dependencies(&roots, &["backend-1".to_string()], &services).unwrap();
+        assert_eq!(resolved, vec!["postgres", "backend-2"]);
+    }
+
+    #[test]
+    fn test_resolve_detects_cycle() {
+        let services = services(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"])]);
+
+        let result = resolve_dependencies(&["a".to_string()], &[], &services);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Dependency cycle detected: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn test_resolve_missing_dependency() {
+        let services = services(&[("backend", &["postgres"])]);
+
+        let result = resolve_dependencies(&["backend".to_string()], &[], &services);
+        let error = result.unwrap_err().downcast::<ShoalError>().unwrap();
+        assert!(matches!(
+            error,
+            ShoalError::MissingService { referenced_by, services }
+                if referenced_by == "Service 'backend'" && services == ["postgres"]
+        ));
+    }
+}
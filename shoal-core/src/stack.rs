@@ -1,20 +1,43 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    compose::ComposeFileManager,
-    docker::{orchestrator::ComposeManager, service::build_docker_service},
-    override_handler::{apply_overrides, extract_override},
+    compose::{ComposeFileManager, OutputFormat},
+    docker::{
+        orchestrator::ComposeManager,
+        service::{build_docker_service, detect_dockerfile, gate_dependencies_on_health},
+    },
+    error::ShoalError,
+    graph::build_graph,
+    override_handler::{
+        OverrideLayer, apply_overrides, apply_service_changes, apply_source_override,
+        extract_override, override_conflicts, target_services,
+    },
+    provenance::attribute_fields,
+    resolver::resolve_dependencies,
     traits::{CommandExecutor, FileSystem, PathProvider},
-    types::{service::Service, stack::Stack, stack_override::StackOverride},
+    types::{
+        docker_service::{DockerComposeFile, DockerService},
+        graph::ServiceGraph,
+        logs::{LogLine, LogOptions},
+        provenance::{FieldLayer, StackProvenance},
+        service::{LocationType, Service},
+        stack::Stack,
+        stack_override::{SourceOverride, StackOverride},
+        stack_status::StackStatus,
+    },
 };
 
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Result, bail};
 use tracing::{debug, error, info};
 
 pub struct StackManager<FS: FileSystem, PP: PathProvider> {
     services: HashMap<String, Service>,
     stacks: HashMap<String, Stack>,
     overrides: HashMap<String, StackOverride>,
+    local_sources: HashMap<String, PathBuf>,
     compose_file_manager: ComposeFileManager<FS, PP>,
     command_executor: std::sync::Arc<dyn CommandExecutor>,
 }
@@ -31,124 +54,545 @@ impl<FS: FileSystem, PP: PathProvider> StackManager<FS, PP> {
             services,
             stacks,
             overrides,
+            local_sources: HashMap::new(),
             compose_file_manager,
             command_executor,
         }
     }
 
-    pub fn up(&self, stack_name: impl Into<String>) -> Result<()> {
-        let (stack_name, override_name) =
-            extract_override(stack_name.into().as_str(), &self.stacks);
+    /// Builds the given services from local checkouts.
+    pub fn set_local_sources(&mut self, local_sources: HashMap<String, PathBuf>) {
+        self.local_sources = local_sources;
+    }
 
-        let stack = self.stacks.get(&stack_name).ok_or_else(|| {
-            anyhow::anyhow!("Failed to find a stack with the name '{stack_name}'.")
-        })?;
+    pub fn stacks(&self) -> &HashMap<String, Stack> {
+        &self.stacks
+    }
 
-        let active_override = if let Some(o) = override_name {
-            let found_override = self
-                .overrides
-                .get(&format!("{}-{}", &stack_name, &o))
-                .ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Failed to find an override for {stack_name} with the name '{o}'."
-                    )
-                })?
-                .clone();
+    pub fn services(&self) -> &HashMap<String, Service> {
+        &self.services
+    }
 
+    pub fn overrides(&self) -> &HashMap<String, StackOverride> {
+        &self.overrides
+    }
+
+    pub fn up(&self, stack_name: impl Into<String>) -> Result<()> {
+        let (stack_name, compose, override_names) = self.build_compose(&stack_name.into())?;
+        if !override_names.is_empty() {
             info!(
-                "Override {o} is being used. To see what changes this makes to the stack, run up using verbose mode (-v|--verbose)."
+                "Overrides {} are being used. To see what changes they make to the stack, run up using verbose mode (-v|--verbose), or -vv to include environment values.",
+                override_names.join(", ")
             );
+        }
+
+        let compose_path = self.compose_file_manager.ensure_compose_path(&stack_name)?;
+        self.compose_file_manager.write_compose_file(
+            &compose,
+            &compose_path,
+            OutputFormat::Yaml,
+        )?;
+
+        let compose_manager =
+            ComposeManager::new(compose_path, stack_name, self.command_executor.clone());
+        compose_manager.up()?;
 
-            Some(found_override)
+        Ok(())
+    }
+
+    /// Resolves the stack and applies overrides without invoking docker.
+    pub fn render(&self, stack_name: impl Into<String>, format: OutputFormat) -> Result<String> {
+        let (_, compose, _) = self.build_compose(&stack_name.into())?;
+        self.compose_file_manager
+            .render_compose_file(&compose, format)
+    }
+
+    /// Resolves the stack and writes its compose file to `path` without invoking docker.
+    pub fn export(
+        &self,
+        stack_name: impl Into<String>,
+        format: OutputFormat,
+        path: &Path,
+    ) -> Result<()> {
+        let (_, compose, _) = self.build_compose(&stack_name.into())?;
+        self.compose_file_manager
+            .write_compose_file(&compose, path, format)?;
+        info!("Compose file written to {}", path.display());
+        Ok(())
+    }
+
+    pub fn down(&self, stack_name: impl Into<String>) -> Result<()> {
+        let compose_manager = self.running_compose(&stack_name.into())?;
+        compose_manager.down()?;
+        Ok(())
+    }
+
+    /// The services a stack, or `stack.override`, runs.
+    pub fn stack_services(&self, stack_name: &str) -> Result<Vec<String>> {
+        let (_, stack, _) = self.stack_layers(stack_name)?;
+        resolve_dependencies(&stack.services, &stack.exclude, &self.services)
+    }
+
+    /// Reports the container state of every service the stack should be running.
+    pub fn status(&self, stack_name: impl Into<String>) -> Result<StackStatus> {
+        let requested = stack_name.into();
+        let (stack_name, _) = extract_override(&requested, &self.stacks);
+        let expected = self.stack_services(&requested)?;
+
+        let compose_path = self.compose_file_manager.compose_file_path(&stack_name)?;
+        let containers = if self.compose_file_manager.file_exists(&compose_path) {
+            ComposeManager::new(compose_path, &stack_name, self.command_executor.clone()).ps()?
         } else {
-            None
+            debug!("No compose file for {stack_name}; treating it as down.");
+            Vec::new()
         };
 
-        self.validate_stack_services(&stack_name, stack)?;
+        Ok(StackStatus::from_containers(
+            stack_name, &expected, containers,
+        ))
+    }
 
-        debug!("Finding docker services for stack {:?}.", stack.services);
+    /// The services a stack, or `stack.override`, starts and how they depend on each other.
+    pub fn graph(&self, stack_name: impl Into<String>) -> Result<ServiceGraph> {
+        let requested = stack_name.into();
+        let (stack_name, compose, _) = self.build_compose(&requested)?;
+        let (_, stack, _) = self.stack_layers(&requested)?;
+        Ok(build_graph(
+            &stack_name,
+            &stack.services,
+            &compose,
+            &self.services,
+        ))
+    }
+
+    /// Which file set each field of a stack's services.
+    pub fn provenance(&self, stack_name: impl Into<String>) -> Result<StackProvenance> {
+        let requested = stack_name.into();
+        let (stack_name, stack, active_overrides) = self.stack_layers(&requested)?;
+        let service_names = self.resolve_stack(&stack_name, &stack)?;
+        let layers = self.override_layers(&stack, &active_overrides, &service_names);
+        let network_name = format!("{stack_name}-network");
+        let docker_services =
+            self.base_services(&stack_name, &service_names, &layers, &network_name)?;
 
-        let network_name = format!("{}-network", stack_name.clone());
+        Ok(StackProvenance {
+            stack: requested,
+            services: attribute_fields(&docker_services, &self.services, &layers),
+        })
+    }
+
+    /// Streams the logs of a running stack, optionally limited to some of its services.
+    pub fn logs(
+        &self,
+        stack_name: impl Into<String>,
+        options: &LogOptions,
+        on_line: &mut dyn FnMut(LogLine),
+    ) -> Result<()> {
+        let requested = stack_name.into();
+        let (stack_name, _) = extract_override(&requested, &self.stacks);
+        let services = self.stack_services(&requested)?;
 
-        let mut docker_services: HashMap<String, _> = stack
+        let unknown: Vec<String> = options
             .services
             .iter()
-            .map(|service_name| {
-                let service = self
-                    .services
-                    .get(service_name)
-                    .expect("Service should exist (validated above)");
-                (
-                    service_name.clone(),
-                    build_docker_service(service, &stack_name, &network_name),
-                )
-            })
+            .filter(|service| !services.contains(service))
+            .cloned()
             .collect();
-
-        if let Some(o) = active_override {
-            apply_overrides(&mut docker_services, &o);
+        if !unknown.is_empty() {
+            bail!(ShoalError::MissingService {
+                referenced_by: format!("Logs for stack '{stack_name}'"),
+                services: unknown,
+            });
         }
 
-        let compose_path = self.compose_file_manager.ensure_compose_path(&stack_name)?;
-        self.compose_file_manager.generate_compose_file(
-            &network_name,
-            docker_services,
-            &compose_path,
-        )?;
+        let compose_manager = self.running_compose(&stack_name)?;
+        compose_manager.logs(options, &mut |prefix, message, stream| {
+            on_line(LogLine {
+                service: prefix.map(|prefix| service_for_prefix(prefix, &stack_name, &services)),
+                message: message.to_string(),
+                stream,
+            })
+        })
+    }
 
-        let compose_manager =
-            ComposeManager::new(compose_path, stack_name, self.command_executor.clone());
-        compose_manager.up()?;
+    /// Runs a command inside one of a running stack's services.
+    pub fn exec(
+        &self,
+        stack_name: impl Into<String>,
+        service: &str,
+        command: &[String],
+    ) -> Result<()> {
+        let compose_manager = self.service_compose(&stack_name.into(), service)?;
+        compose_manager.exec(service, command)
+    }
 
-        Ok(())
+    /// Opens an interactive shell in one of a running stack's services.
+    pub fn shell(&self, stack_name: impl Into<String>, service: &str) -> Result<()> {
+        let compose_manager = self.service_compose(&stack_name.into(), service)?;
+        let shell = compose_manager.find_shell(service)?;
+        compose_manager.exec(service, &[shell.to_string()])
     }
 
-    pub fn down(&self, stack_name: impl Into<String>) -> Result<()> {
-        let stack_name = stack_name.into();
+    pub fn restart(&self, stack_name: impl Into<String>, service: &str) -> Result<()> {
+        let compose_manager = self.service_compose(&stack_name.into(), service)?;
+        compose_manager.restart(&[service.to_string()])
+    }
+
+    /// Rebuilds a Local-source service and recreates its container.
+    pub fn rebuild(
+        &self,
+        stack_name: impl Into<String>,
+        service: &str,
+        with_dependents: bool,
+    ) -> Result<()> {
+        let requested = stack_name.into();
+        let (stack_name, _) = extract_override(&requested, &self.stacks);
+        let compose_manager = self.service_compose(&requested, service)?;
+
+        // The generated file describes what is actually running, which may differ from the
+        // current configuration if it changed since the stack was brought up.
         let compose_path = self.compose_file_manager.compose_file_path(&stack_name)?;
+        let compose = self.compose_file_manager.read_compose_file(&compose_path)?;
+        let generated_services = &compose["services"];
+        if generated_services[service].get("build").is_none() {
+            bail!(
+                "Service '{service}' in stack '{stack_name}' is not built from a local source; use restart instead."
+            );
+        }
+
+        let mut recreate = vec![service.to_string()];
+        if with_dependents {
+            recreate.extend(generated_dependents(generated_services, service));
+        }
+
+        compose_manager.rebuild(&[service.to_string()], &recreate)
+    }
+
+    fn service_compose(&self, stack_name: &str, service: &str) -> Result<ComposeManager> {
+        let services = self.stack_services(stack_name)?;
+        let (stack_name, _) = extract_override(stack_name, &self.stacks);
+        if !services.iter().any(|s| s == service) {
+            bail!(ShoalError::MissingService {
+                referenced_by: format!("Stack '{stack_name}'"),
+                services: vec![service.to_string()],
+            });
+        }
+        self.running_compose(&stack_name)
+    }
+
+    fn running_compose(&self, stack_name: &str) -> Result<ComposeManager> {
+        let compose_path = self.compose_file_manager.compose_file_path(stack_name)?;
         if !self.compose_file_manager.file_exists(&compose_path) {
-            bail!("Stack {stack_name} is not running; compose file missing at {compose_path:?}");
+            bail!(ShoalError::StackNotRunning {
+                stack: stack_name.to_string(),
+                compose_path,
+            });
         }
 
-        let compose_manager =
-            ComposeManager::new(compose_path, stack_name, self.command_executor.clone());
-        compose_manager.down()?;
-        Ok(())
+        Ok(ComposeManager::new(
+            compose_path,
+            stack_name,
+            self.command_executor.clone(),
+        ))
+    }
+
+    pub(crate) fn build_compose(
+        &self,
+        stack_name: &str,
+    ) -> Result<(String, DockerComposeFile, Vec<&str>)> {
+        let (stack_name, stack, active_overrides) = self.stack_layers(stack_name)?;
+        let service_names = self.resolve_stack(&stack_name, &stack)?;
+        let layers = self.override_layers(&stack, &active_overrides, &service_names);
+        let network_name = format!("{stack_name}-network");
+        let mut docker_services =
+            self.base_services(&stack_name, &service_names, &layers, &network_name)?;
+
+        let named_layers: Vec<&OverrideLayer> = layers
+            .iter()
+            .filter(|layer| layer.layer == FieldLayer::Override)
+            .collect();
+        for (i, later) in named_layers.iter().enumerate() {
+            // Groups from the same override follow its own selector precedence.
+            for earlier in named_layers[..i].iter().filter(|e| e.name != later.name) {
+                for (service, field) in override_conflicts(&earlier.overrides, &later.overrides) {
+                    debug!(
+                        "Override {} replaces {field} of {service} set by override {}",
+                        later.name, earlier.name
+                    );
+                }
+            }
+        }
+        for layer in &layers {
+            apply_overrides(&mut docker_services, &layer.overrides);
+        }
+
+        gate_dependencies_on_health(&mut docker_services);
+
+        let compose = self
+            .compose_file_manager
+            .build_compose_file(&network_name, docker_services);
+
+        let override_names = active_overrides.iter().map(|o| o.name.as_str()).collect();
+
+        Ok((stack_name, compose, override_names))
+    }
+
+    fn stack_layers(&self, stack_name: &str) -> Result<(String, Stack, Vec<&StackOverride>)> {
+        let (stack_name, override_names) = extract_override(stack_name, &self.stacks);
+
+        let stack = self
+            .stacks
+            .get(&stack_name)
+            .ok_or_else(|| ShoalError::UnknownStack {
+                stack: stack_name.clone(),
+            })?;
+
+        let named_overrides = override_names
+            .into_iter()
+            .map(|o| {
+                let found_override = self
+                    .overrides
+                    .get(&StackOverride::key_for(Some(&stack_name), &o))
+                    .or_else(|| self.overrides.get(&StackOverride::key_for(None, &o)))
+                    .ok_or_else(|| ShoalError::UnknownOverride {
+                        stack: stack_name.clone(),
+                        name: o.clone(),
+                    })?;
+
+                debug!("Using override {o} for stack {stack_name}");
+                Ok(found_override)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut active_overrides: Vec<&StackOverride> = self
+            .overrides
+            .values()
+            .filter(|o| {
+                o.auto_applies_to(&stack_name)
+                    && !named_overrides.iter().any(|named| named.key() == o.key())
+            })
+            .collect();
+        active_overrides.sort_by_key(|o| o.label());
+        for o in &active_overrides {
+            debug!(
+                "Applying override {} to stack {stack_name} automatically",
+                o.name
+            );
+        }
+        active_overrides.extend(named_overrides);
+
+        let stack = apply_service_changes(stack, &active_overrides);
+        Ok((stack_name, stack, active_overrides))
+    }
+
+    fn resolve_stack(&self, stack_name: &str, stack: &Stack) -> Result<Vec<String>> {
+        self.validate_stack_services(stack_name, stack)?;
+
+        debug!("Finding docker services for stack {:?}.", stack.services);
+
+        resolve_dependencies(&stack.services, &stack.exclude, &self.services)
+    }
+
+    fn override_layers<'a>(
+        &self,
+        stack: &'a Stack,
+        active_overrides: &[&'a StackOverride],
+        service_names: &[String],
+    ) -> Vec<OverrideLayer<'a>> {
+        let services: Vec<&Service> = service_names
+            .iter()
+            .filter_map(|name| self.services.get(name))
+            .collect();
+
+        let stack_layer = (
+            FieldLayer::Stack,
+            stack.name.as_str(),
+            stack.origin.as_ref(),
+            &stack.overrides,
+        );
+        let override_layers = active_overrides.iter().map(|o| {
+            (
+                FieldLayer::Override,
+                o.name.as_str(),
+                o.origin.as_ref(),
+                &o.overrides,
+            )
+        });
+
+        std::iter::once(stack_layer)
+            .chain(override_layers)
+            .flat_map(|(layer, name, source, overrides)| {
+                target_services(overrides, &services)
+                    .into_iter()
+                    .map(move |overrides| OverrideLayer {
+                        layer,
+                        name,
+                        source,
+                        overrides,
+                    })
+            })
+            .collect()
+    }
+
+    fn base_services(
+        &self,
+        stack_name: &str,
+        service_names: &[String],
+        layers: &[OverrideLayer],
+        network_name: &str,
+    ) -> Result<HashMap<String, DockerService>> {
+        service_names
+            .iter()
+            .map(|service_name| {
+                let service = self.stack_service(service_name, layers)?;
+                Ok((
+                    service_name.clone(),
+                    self.build_stack_service(&service, stack_name, network_name, service_names),
+                ))
+            })
+            .collect()
+    }
+
+    fn stack_service(&self, service_name: &str, layers: &[OverrideLayer]) -> Result<Service> {
+        let mut service = self
+            .services
+            .get(service_name)
+            .expect("Service should exist (resolved above)")
+            .clone();
+
+        for layer in layers {
+            let source_override = layer
+                .overrides
+                .get(service_name)
+                .and_then(|service_override| service_override.source.as_ref());
+            if let Some(source_override) = source_override {
+                let base_dir = layer.source.and_then(|source| source.path.parent());
+                apply_source_override(&mut service, source_override, base_dir)?;
+            }
+        }
+
+        if let Some(path) = self.local_sources.get(service_name) {
+            debug!("Building {service_name} from {}", path.display());
+            let local_source = SourceOverride {
+                r#type: Some(LocationType::Local),
+                location: Some(path.to_string_lossy().to_string()),
+                ..SourceOverride::default()
+            };
+            apply_source_override(&mut service, &local_source, None)?;
+        }
+
+        Ok(service)
+    }
+
+    fn build_stack_service(
+        &self,
+        service: &Service,
+        stack_name: &str,
+        network_name: &str,
+        stack_services: &[String],
+    ) -> DockerService {
+        let service_name = &service.service_name;
+        let mut docker_service = build_docker_service(service, stack_name, network_name);
+
+        if let Some(build) = docker_service.build.as_mut()
+            && build.dockerfile.is_none()
+        {
+            build.dockerfile = detect_dockerfile(Path::new(&build.context), |path| {
+                self.compose_file_manager.file_exists(path)
+            });
+            debug!(
+                "Using {:?} to build {service_name} from {}",
+                build.dockerfile, build.context
+            );
+        }
+
+        if let Some(depends_on) = docker_service.depends_on.as_mut() {
+            depends_on.retain(|dependency, _| stack_services.contains(dependency));
+        }
+
+        docker_service
     }
 
     fn validate_stack_services(&self, stack_name: &str, stack: &Stack) -> Result<()> {
-        let missing: Vec<&String> = stack
+        let missing: Vec<String> = stack
             .services
             .iter()
             .filter(|service_name| !self.services.contains_key(*service_name))
+            .cloned()
             .collect();
 
         if !missing.is_empty() {
             error!("Stack '{stack_name}' references non-existent services: {missing:?}");
-            return Err(anyhow!(
-                "Stack '{stack_name}' references non-existent services: {missing:?}"
-            ));
+            bail!(ShoalError::MissingService {
+                referenced_by: format!("Stack '{stack_name}'"),
+                services: missing,
+            });
         }
 
         Ok(())
     }
 }
 
+fn generated_dependents(generated_services: &serde_json::Value, service: &str) -> Vec<String> {
+    let services = generated_services
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, body)| {
+            let depends_on = serde_json::from_value(body["depends_on"].clone()).ok();
+            let docker_service = DockerService {
+                depends_on,
+                ..DockerService::default()
+            };
+            (name.clone(), docker_service)
+        })
+        .collect();
+
+    DockerComposeFile {
+        services,
+        networks: HashMap::new(),
+    }
+    .dependents_of(service)
+}
+
+fn service_for_prefix(prefix: &str, stack_name: &str, services: &[String]) -> String {
+    let without_stack = prefix.strip_prefix(&format!("{stack_name}-"));
+    [Some(prefix), without_stack]
+        .into_iter()
+        .flatten()
+        .flat_map(|candidate| {
+            let without_index = candidate
+                .rsplit_once('-')
+                .filter(|(_, index)| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+                .map(|(name, _)| name);
+            [Some(candidate), without_index]
+        })
+        .flatten()
+        .find(|candidate| services.iter().any(|service| service == candidate))
+        .unwrap_or(prefix)
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::traits::mocks::{MockCommandExecutor, MockFileSystem, MockPathProvider};
+    use crate::types::config_source::{ConfigSource, FileScope};
     use crate::types::service::{LocationType, Service, ServiceLocation};
+    use crate::types::stack_override::Override;
+    use crate::types::stack_status::StackState;
     use std::sync::Arc;
 
     fn create_test_service(name: &str) -> Service {
         Service {
             service_name: name.to_string(),
-            source: ServiceLocation {
+            source: Some(ServiceLocation {
                 r#type: LocationType::Image,
                 location: "test/image:latest".to_string(),
-            },
+                dockerfile: None,
+            }),
             internal_ports: vec!["8080".to_string()],
+            ..Service::default()
         }
     }
 
@@ -157,9 +601,597 @@ mod tests {
             name: name.to_string(),
             description: "Test stack".to_string(),
             services,
+            exclude: vec![],
+            overrides: HashMap::new(),
+            origin: None,
+            shadowed: Vec::new(),
         }
     }
 
+    fn create_test_manager(
+        services: HashMap<String, Service>,
+        stacks: HashMap<String, Stack>,
+        command_executor: Arc<MockCommandExecutor>,
+    ) -> StackManager<MockFileSystem, MockPathProvider> {
+        let compose_file_manager =
+            ComposeFileManager::new(MockFileSystem::new(), MockPathProvider::new());
+        StackManager::new(
+            services,
+            stacks,
+            HashMap::new(),
+            compose_file_manager,
+            command_executor,
+        )
+    }
+
+    #[test]
+    fn test_render_does_not_invoke_docker() {
+        let mut frontend = create_test_service("frontend");
+        frontend.dependencies = vec!["backend".to_string()];
+
+        let mut services = HashMap::new();
+        services.insert("frontend".to_string(), frontend);
+        services.insert("backend".to_string(), create_test_service("backend"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["frontend".to_string()]),
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = create_test_manager(services, stacks, command_executor.clone());
+
+        let rendered = manager.render("test-stack", OutputFormat::Json).unwrap();
+        let compose: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(
+            compose["services"]["frontend"]["depends_on"],
+            serde_json::json!({ "backend": { "condition": "service_started" } })
+        );
+        assert_eq!(
+            compose["services"]["backend"]["container_name"],
+            "test-stack-backend"
+        );
+
+        let rendered = manager.render("test-stack", OutputFormat::Yaml).unwrap();
+        assert!(rendered.contains("test-stack-network"));
+        assert!(command_executor.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chained_overrides_apply_in_order() {
+        let services = HashMap::from([("api".to_string(), create_test_service("api"))]);
+        let stacks = HashMap::from([(
+            "web".to_string(),
+            create_test_stack("web", vec!["api".to_string()]),
+        )]);
+        let named_override = |name: &str, log: &str| StackOverride {
+            name: name.to_string(),
+            stack: Some("web".to_string()),
+            description: String::new(),
+            auto_apply: false,
+            overrides: HashMap::from([(
+                "api".to_string(),
+                Override {
+                    env: Some(HashMap::from([
+                        ("LOG".to_string(), log.to_string()),
+                        (name.to_uppercase(), "1".to_string()),
+                    ])),
+                    ..Default::default()
+                },
+            )]),
+            add_services: vec![],
+            remove_services: vec![],
+            origin: None,
+            shadowed: Vec::new(),
+        };
+        let overrides = HashMap::from([
+            ("web-debug".to_string(), named_override("debug", "debug")),
+            ("web-trace".to_string(), named_override("trace", "trace")),
+        ]);
+        let manager = StackManager::new(
+            services,
+            stacks,
+            overrides,
+            ComposeFileManager::new(MockFileSystem::new(), MockPathProvider::new()),
+            Arc::new(MockCommandExecutor::new()),
+        );
+
+        let render = |stack_name: &str| {
+            let rendered = manager.render(stack_name, OutputFormat::Json).unwrap();
+            let compose: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+            compose["services"]["api"]["environment"].clone()
+        };
+
+        assert_eq!(
+            render("web.debug.trace"),
+            serde_json::json!({ "LOG": "trace", "DEBUG": "1", "TRACE": "1" })
+        );
+        assert_eq!(render("web.trace.debug")["LOG"], "debug");
+        assert!(matches!(
+            manager.render("web.debug.missing", OutputFormat::Json),
+            Err(e) if matches!(
+                e.downcast_ref::<ShoalError>(),
+                Some(ShoalError::UnknownOverride { name, .. }) if name == "missing"
+            )
+        ));
+    }
+
+    #[test]
+    fn test_overrides_add_and_remove_services() {
+        let mut api = create_test_service("api");
+        api.dependencies = vec!["payments".to_string(), "postgres".to_string()];
+        let services = HashMap::from([
+            ("api".to_string(), api),
+            ("payments".to_string(), create_test_service("payments")),
+            ("postgres".to_string(), create_test_service("postgres")),
+            ("mailhog".to_string(), create_test_service("mailhog")),
+        ]);
+        let stacks = HashMap::from([(
+            "web".to_string(),
+            create_test_stack("web", vec!["api".to_string()]),
+        )]);
+        let overrides = HashMap::from([(
+            "web-stubbed".to_string(),
+            StackOverride {
+                name: "stubbed".to_string(),
+                stack: Some("web".to_string()),
+                description: String::new(),
+                auto_apply: false,
+                overrides: HashMap::new(),
+                add_services: vec!["mailhog".to_string()],
+                remove_services: vec!["payments".to_string()],
+                origin: None,
+                shadowed: Vec::new(),
+            },
+        )]);
+        let manager = StackManager::new(
+            services,
+            stacks,
+            overrides,
+            ComposeFileManager::new(MockFileSystem::new(), MockPathProvider::new()),
+            Arc::new(MockCommandExecutor::new()),
+        );
+
+        assert_eq!(
+            manager.stack_services("web").unwrap(),
+            vec!["payments", "postgres", "api"]
+        );
+        assert_eq!(
+            manager.stack_services("web.stubbed").unwrap(),
+            vec!["postgres", "api", "mailhog"]
+        );
+
+        let rendered = manager.render("web.stubbed", OutputFormat::Json).unwrap();
+        let compose: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert!(compose["services"]["payments"].is_null());
+        assert_eq!(
+            compose["services"]["api"]["depends_on"],
+            serde_json::json!({ "postgres": { "condition": "service_started" } })
+        );
+        assert_eq!(
+            compose["services"]["mailhog"]["container_name"],
+            "web-mailhog"
+        );
+    }
+
+    #[test]
+    fn test_overrides_for_any_stack_target_selectors() {
+        let mut api = create_test_service("api");
+        api.tags = vec!["aws".to_string()];
+        let mut worker = create_test_service("worker");
+        worker.tags = vec!["aws".to_string()];
+        let services = HashMap::from([
+            ("api".to_string(), api),
+            ("worker".to_string(), worker),
+            ("postgres".to_string(), create_test_service("postgres")),
+        ]);
+        let stacks = HashMap::from([(
+            "web".to_string(),
+            create_test_stack(
+                "web",
+                vec![
+                    "api".to_string(),
+                    "worker".to_string(),
+                    "postgres".to_string(),
+                ],
+            ),
+        )]);
+        let any_stack_override = |name: &str, scope, overrides| StackOverride {
+            name: name.to_string(),
+            stack: None,
+            description: String::new(),
+            overrides,
+            auto_apply: true,
+            add_services: vec![],
+            remove_services: vec![],
+            origin: Some(ConfigSource {
+                scope,
+                path: PathBuf::from(format!("/overrides/{name}.yml")),
+            }),
+            shadowed: Vec::new(),
+        };
+        let env = |key: &str, value: &str| Override {
+            env: Some(HashMap::from([(key.to_string(), value.to_string())])),
+            ..Default::default()
+        };
+        let mut aws = any_stack_override(
+            "aws",
+            FileScope::Local,
+            HashMap::from([
+                (
+                    "tag:aws".to_string(),
+                    Override {
+                        volumes: Some(vec!["~/.aws:/root/.aws".to_string()]),
+                        ..env("AWS_PROFILE", "dev")
+                    },
+                ),
+                ("worker".to_string(), env("AWS_PROFILE", "worker")),
+            ]),
+        );
+        aws.auto_apply = false;
+        let quiet = any_stack_override(
+            "quiet",
+            FileScope::Global,
+            HashMap::from([("*".to_string(), env("LOG", "warn"))]),
+        );
+        let ignored = any_stack_override(
+            "ignored",
+            FileScope::Local,
+            HashMap::from([("*".to_string(), env("LOG", "trace"))]),
+        );
+        let overrides = [aws, quiet, ignored]
+            .into_iter()
+            .map(|o| (o.key(), o))
+            .collect();
+        let manager = StackManager::new(
+            services,
+            stacks,
+            overrides,
+            ComposeFileManager::new(MockFileSystem::new(), MockPathProvider::new()),
+            Arc::new(MockCommandExecutor::new()),
+        );
+
+        let render = |stack_name: &str| {
+            let rendered = manager.render(stack_name, OutputFormat::Json).unwrap();
+            let compose: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+            compose["services"].clone()
+        };
+
+        let services = render("web");
+        assert_eq!(services["postgres"]["environment"]["LOG"], "warn");
+        assert!(services["api"]["volumes"].is_null());
+
+        let services = render("web.aws");
+        assert_eq!(services["api"]["environment"]["AWS_PROFILE"], "dev");
+        assert_eq!(services["worker"]["environment"]["AWS_PROFILE"], "worker");
+        assert_eq!(
+            services["worker"]["volumes"],
+            serde_json::json!(["~/.aws:/root/.aws"])
+        );
+        assert!(services["postgres"]["volumes"].is_null());
+        assert_eq!(services["api"]["environment"]["LOG"], "warn");
+    }
+
+    #[test]
+    fn test_status_flags_missing_services() {
+        let mut frontend = create_test_service("frontend");
+        frontend.dependencies = vec!["backend".to_string(), "postgres".to_string()];
+
+        let mut services = HashMap::new();
+        services.insert("frontend".to_string(), frontend);
+        services.insert("backend".to_string(), create_test_service("backend"));
+        services.insert("postgres".to_string(), create_test_service("postgres"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["frontend".to_string()]),
+        );
+
+        let file_system = MockFileSystem::new();
+        file_system.files.lock().unwrap().insert(
+            "/test/data/shoal/stacks/test-stack/docker-compose.generated.yml".into(),
+            String::new(),
+        );
+        let command_executor = MockCommandExecutor::new();
+        command_executor.push_stdout(
+            [
+            r#"{"Name":"test-stack-frontend","Service":"frontend","State":"running","Health":"","RunningFor":"2 minutes ago","Publishers":[{"URL":"0.0.0.0","TargetPort":80,"PublishedPort":8080,"Protocol":"tcp"},{"URL":"::","TargetPort":80,"PublishedPort":8080,"Protocol":"tcp"}]}"#,
+            r#"{"Name":"test-stack-postgres","Service":"postgres","State":"running","Health":"starting","RunningFor":"2 minutes ago","Publishers":[]}"#,
+            ]
+            .join("\n"),
+        );
+        let command_executor = Arc::new(command_executor);
+
+        let manager = StackManager::new(
+            services,
+            stacks,
+            HashMap::new(),
+            ComposeFileManager::new(file_system, MockPathProvider::new()),
+            command_executor.clone(),
+        );
+
+        let status = manager.status("test-stack").unwrap();
+        assert_eq!(status.state, StackState::Degraded);
+
+        let backend = status
+            .services
+            .iter()
+            .find(|s| s.service == "backend")
+            .unwrap();
+        assert!(backend.missing);
+
+        let frontend = status
+            .services
+            .iter()
+            .find(|s| s.service == "frontend")
+            .unwrap();
+        assert_eq!(frontend.ports, vec!["8080->80/tcp"]);
+        assert_eq!(frontend.uptime, Some("2 minutes".to_string()));
+        assert!(frontend.is_ready());
+
+        let postgres = status
+            .services
+            .iter()
+            .find(|s| s.service == "postgres")
+            .unwrap();
+        assert!(!postgres.is_ready());
+
+        let calls = command_executor.calls.lock().unwrap();
+        assert!(calls[0].1.contains(&"ps".to_string()));
+    }
+
+    #[test]
+    fn test_logs_maps_prefixes_to_services() {
+        let mut services = HashMap::new();
+        services.insert("frontend".to_string(), create_test_service("frontend"));
+        services.insert("backend".to_string(), create_test_service("backend"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack(
+                "test-stack",
+                vec!["frontend".to_string(), "backend".to_string()],
+            ),
+        );
+
+        let file_system = MockFileSystem::new();
+        file_system.files.lock().unwrap().insert(
+            "/test/data/shoal/stacks/test-stack/docker-compose.generated.yml".into(),
+            String::new(),
+        );
+        let command_executor = MockCommandExecutor::new();
+        command_executor.push_stdout("test-stack-frontend  | listening\nbackend-1  | ready\n");
+        let command_executor = Arc::new(command_executor);
+
+        let manager = StackManager::new(
+            services,
+            stacks,
+            HashMap::new(),
+            ComposeFileManager::new(file_system, MockPathProvider::new()),
+            command_executor.clone(),
+        );
+
+        let options = LogOptions {
+            follow: true,
+            tail: Some("10".to_string()),
+            ..Default::default()
+        };
+        let mut lines = Vec::new();
+        manager
+            .logs("test-stack", &options, &mut |line| lines.push(line))
+            .unwrap();
+
+        assert_eq!(lines[0].service.as_deref(), Some("frontend"));
+        assert_eq!(lines[0].message, "listening");
+        assert_eq!(lines[1].service.as_deref(), Some("backend"));
+
+        let calls = command_executor.calls.lock().unwrap();
+        let args = &calls[0].1;
+        assert!(args.contains(&"logs".to_string()));
+        assert!(args.contains(&"--follow".to_string()));
+        assert!(args.ends_with(&["--tail".to_string(), "10".to_string()]));
+    }
+
+    #[test]
+    fn test_logs_rejects_services_outside_stack() {
+        let mut services = HashMap::new();
+        services.insert("service1".to_string(), create_test_service("service1"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["service1".to_string()]),
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = create_test_manager(services, stacks, command_executor.clone());
+
+        let options = LogOptions {
+            services: vec!["worker".to_string()],
+            ..Default::default()
+        };
+        let error = manager
+            .logs("test-stack", &options, &mut |_| {})
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ShoalError>(),
+            Some(ShoalError::MissingService { services, .. }) if services == &["worker"]
+        ));
+        assert!(command_executor.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shell_execs_first_available_shell() {
+        let mut services = HashMap::new();
+        services.insert("postgres".to_string(), create_test_service("postgres"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["postgres".to_string()]),
+        );
+
+        let file_system = MockFileSystem::new();
+        file_system.files.lock().unwrap().insert(
+            "/test/data/shoal/stacks/test-stack/docker-compose.generated.yml".into(),
+            String::new(),
+        );
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = StackManager::new(
+            services,
+            stacks,
+            HashMap::new(),
+            ComposeFileManager::new(file_system, MockPathProvider::new()),
+            command_executor.clone(),
+        );
+
+        manager.shell("test-stack", "postgres").unwrap();
+
+        let calls = command_executor.calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[1].1.ends_with(&[
+            "exec".to_string(),
+            "postgres".to_string(),
+            "bash".to_string()
+        ]));
+        assert!(calls[1].1.contains(&"test-stack".to_string()));
+    }
+
+    #[test]
+    fn test_exec_rejects_service_outside_stack() {
+        let mut services = HashMap::new();
+        services.insert("service1".to_string(), create_test_service("service1"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["service1".to_string()]),
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = create_test_manager(services, stacks, command_executor.clone());
+
+        let error = manager
+            .exec("test-stack", "service2", &["ls".to_string()])
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ShoalError>(),
+            Some(ShoalError::MissingService { .. })
+        ));
+        assert!(command_executor.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_recreates_dependents() {
+        let mut backend = create_test_service("backend");
+        backend.source = Some(ServiceLocation {
+            r#type: LocationType::Local,
+            location: "./backend".to_string(),
+            dockerfile: None,
+        });
+        let mut frontend = create_test_service("frontend");
+        frontend.dependencies = vec!["backend".to_string()];
+
+        let mut services = HashMap::new();
+        services.insert("backend".to_string(), backend);
+        services.insert("frontend".to_string(), frontend);
+        // Compose syntax Shoal passes through untyped mustn't stop other services rebuilding.
+        let mut postgres = create_test_service("postgres");
+        postgres.compose = Some(serde_json::Map::from_iter([(
+            "environment".to_string(),
+            serde_json::json!(["POSTGRES_PASSWORD"]),
+        )]));
+        services.insert("postgres".to_string(), postgres);
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack(
+                "test-stack",
+                vec!["frontend".to_string(), "postgres".to_string()],
+            ),
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let file_system = MockFileSystem::new();
+        let manager = StackManager::new(
+            services,
+            stacks,
+            HashMap::new(),
+            ComposeFileManager::new(file_system.clone(), MockPathProvider::new()),
+            command_executor.clone(),
+        );
+        manager.up("test-stack").unwrap();
+
+        manager.rebuild("test-stack", "backend", true).unwrap();
+
+        let calls = command_executor.calls.lock().unwrap();
+        assert!(
+            calls[1]
+                .1
+                .ends_with(&["build".to_string(), "backend".to_string()])
+        );
+        assert!(calls[2].1.ends_with(&[
+            "--wait".to_string(),
+            "backend".to_string(),
+            "frontend".to_string()
+        ]));
+    }
+
+    #[test]
+    fn test_rebuild_rejects_image_services() {
+        let mut services = HashMap::new();
+        services.insert("postgres".to_string(), create_test_service("postgres"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["postgres".to_string()]),
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = create_test_manager(services, stacks, command_executor.clone());
+        manager.up("test-stack").unwrap();
+
+        assert!(manager.rebuild("test-stack", "postgres", false).is_err());
+        assert_eq!(command_executor.calls.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_service_for_prefix() {
+        let services = vec!["api".to_string(), "api-gateway".to_string()];
+        assert_eq!(service_for_prefix("full-api", "full", &services), "api");
+        assert_eq!(service_for_prefix("api-1", "full", &services), "api");
+        assert_eq!(
+            service_for_prefix("full-api-gateway", "full", &services),
+            "api-gateway"
+        );
+        assert_eq!(service_for_prefix("other-2", "full", &services), "other-2");
+    }
+
+    #[test]
+    fn test_status_without_compose_file_is_down() {
+        let mut services = HashMap::new();
+        services.insert("service1".to_string(), create_test_service("service1"));
+
+        let mut stacks = HashMap::new();
+        stacks.insert(
+            "test-stack".to_string(),
+            create_test_stack("test-stack", vec!["service1".to_string()]),
+        );
+
+        let command_executor = Arc::new(MockCommandExecutor::new());
+        let manager = create_test_manager(services, stacks, command_executor.clone());
+
+        let status = manager.status("test-stack").unwrap();
+        assert_eq!(status.state, StackState::Down);
+        assert!(status.services[0].missing);
+        assert!(command_executor.calls.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn test_validate_stack_services_success() {
         let mut services = HashMap::new();
@@ -1,24 +1,234 @@
-use anyhow::{Result, anyhow};
-use std::process::Command;
+use anyhow::Result;
+
+use crate::error::ShoalError;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use tracing::error;
 
 pub trait CommandExecutor: Send + Sync {
+    /// Runs the command with inherited stdio, failing on a non-zero exit code.
     fn execute(&self, program: &str, args: &[&str]) -> Result<()>;
+
+    /// Runs the command to completion and returns what it wrote. A non-zero exit code is
+    /// reported in the output rather than as an error; see [`CommandOutput::into_stdout`].
+    fn capture(&self, program: &str, args: &[&str]) -> Result<CommandOutput>;
+
+    /// Runs the command, handing each stdout and stderr line to `on_line` as it arrives.
+    /// Fails on a non-zero exit code, like `execute`.
+    fn stream(
+        &self,
+        program: &str,
+        args: &[&str],
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<()>;
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the process was terminated by a signal.
+    pub code: Option<i32>,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Returns stdout, or `ShoalError::CommandFailed` if the command did not succeed.
+    pub fn into_stdout(self, program: &str) -> Result<String> {
+        if self.success() {
+            Ok(self.stdout)
+        } else {
+            Err(ShoalError::CommandFailed {
+                command: program.to_string(),
+                code: self.code,
+            }
+            .into())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
 pub struct StdCommandExecutor;
 
 impl CommandExecutor for StdCommandExecutor {
     fn execute(&self, program: &str, args: &[&str]) -> Result<()> {
-        let status = Command::new(program).args(args).status()?;
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .map_err(|e| not_run(program, "run", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(ShoalError::CommandFailed {
+                command: program.to_string(),
+                code: status.code(),
+            }
+            .into())
+        }
+    }
+
+    fn capture(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| not_run(program, "run", e))?;
+
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            code: output.status.code(),
+        })
+    }
+
+    fn stream(
+        &self,
+        program: &str,
+        args: &[&str],
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<()> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| not_run(program, "run", e))?;
+
+        // Both pipes are drained on their own thread so neither can fill up and block the
+        // child; lines are funnelled back here so `on_line` runs on the caller's thread.
+        let (sender, receiver) = mpsc::channel();
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|stdout| spawn_reader(stdout, OutputStream::Stdout, sender.clone())),
+            child
+                .stderr
+                .take()
+                .map(|stderr| spawn_reader(stderr, OutputStream::Stderr, sender.clone())),
+        ];
+        drop(sender);
+
+        for line in receiver {
+            on_line(line);
+        }
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+
+        let status = child.wait().map_err(|e| not_run(program, "wait on", e))?;
 
         if status.success() {
             Ok(())
         } else {
-            Err(anyhow!(
-                "Command '{}' failed with exit code: {:?}",
-                program,
-                status.code()
-            ))
+            Err(ShoalError::CommandFailed {
+                command: program.to_string(),
+                code: status.code(),
+            }
+            .into())
+        }
+    }
+}
+
+fn not_run(program: &str, action: &str, error: std::io::Error) -> anyhow::Error {
+    error!("Failed to {action} '{program}': {error}");
+    ShoalError::CommandFailed {
+        command: program.to_string(),
+        code: None,
+    }
+    .into()
+}
+
+fn spawn_reader(
+    pipe: impl Read + Send + 'static,
+    stream: OutputStream,
+    sender: mpsc::Sender<OutputLine>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let Ok(text) = line else { break };
+            if sender.send(OutputLine { stream, text }).is_err() {
+                break;
+            }
         }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_program_is_command_failed() {
+        let program = "shoal-test-no-such-program";
+        let executor = StdCommandExecutor;
+
+        for error in [
+            executor.execute(program, &[]).unwrap_err(),
+            executor.capture(program, &[]).unwrap_err(),
+            executor.stream(program, &[], &mut |_| {}).unwrap_err(),
+        ] {
+            assert!(matches!(
+                error.downcast_ref::<ShoalError>(),
+                Some(ShoalError::CommandFailed { command, code: None }) if command == program
+            ));
+        }
+    }
+
+    #[test]
+    fn test_into_stdout_maps_failure_to_command_failed() {
+        let output = CommandOutput {
+            stdout: String::new(),
+            stderr: "no such service".to_string(),
+            code: Some(1),
+        };
+
+        let error = output.into_stdout("docker").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ShoalError>(),
+            Some(ShoalError::CommandFailed { code: Some(1), .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_std_executor_captures_and_streams() {
+        let executor = StdCommandExecutor;
+        let script = "echo out; echo err >&2; exit 3";
+
+        let output = executor.capture("sh", &["-c", script]).unwrap();
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+        assert_eq!(output.code, Some(3));
+
+        let mut lines = Vec::new();
+        let result = executor.stream("sh", &["-c", script], &mut |line| lines.push(line));
+        assert!(result.is_err());
+        assert!(lines.contains(&OutputLine {
+            stream: OutputStream::Stdout,
+            text: "out".to_string(),
+        }));
+        assert!(lines.contains(&OutputLine {
+            stream: OutputStream::Stderr,
+            text: "err".to_string(),
+        }));
     }
 }
@@ -9,6 +9,7 @@ pub trait FileSystem: Send + Sync {
     fn exists(&self, path: &Path) -> bool;
 }
 
+#[derive(Clone, Copy, Debug, Default)]
 pub struct StdFileSystem;
 
 impl FileSystem for StdFileSystem {
@@ -1,12 +1,19 @@
+use super::command::{CommandOutput, OutputLine, OutputStream};
 use super::{CommandExecutor, FileSystem, PathProvider};
+use crate::error::ShoalError;
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+type CommandCalls = Arc<Mutex<Vec<(String, Vec<String>)>>>;
+
+#[derive(Clone, Default)]
 pub struct MockCommandExecutor {
-    pub calls: Arc<Mutex<Vec<(String, Vec<String>)>>>,
+    pub calls: CommandCalls,
     pub should_fail: bool,
+    /// Outputs handed out in order to `capture` and `stream` calls.
+    pub outputs: Arc<Mutex<VecDeque<CommandOutput>>>,
 }
 
 impl MockCommandExecutor {
@@ -14,12 +21,36 @@ impl MockCommandExecutor {
         Self {
             calls: Arc::new(std::sync::Mutex::new(Vec::new())),
             should_fail: false,
+            outputs: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
-}
 
-impl CommandExecutor for MockCommandExecutor {
-    fn execute(&self, program: &str, args: &[&str]) -> Result<()> {
+    /// Queues the output returned by the next `capture` or `stream` call.
+    pub fn push_output(&self, output: CommandOutput) {
+        self.outputs.lock().unwrap().push_back(output);
+    }
+
+    /// Queues a successful output with the given stdout.
+    pub fn push_stdout(&self, stdout: impl Into<String>) {
+        self.push_output(CommandOutput {
+            stdout: stdout.into(),
+            stderr: String::new(),
+            code: Some(0),
+        });
+    }
+
+    fn next_output(&self) -> CommandOutput {
+        self.outputs
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(CommandOutput {
+                code: Some(0),
+                ..Default::default()
+            })
+    }
+
+    fn record(&self, program: &str, args: &[&str]) -> Result<()> {
         let args_vec: Vec<String> = args.iter().map(|s| s.to_string()).collect();
         self.calls
             .lock()
@@ -34,6 +65,51 @@ impl CommandExecutor for MockCommandExecutor {
     }
 }
 
+impl CommandExecutor for MockCommandExecutor {
+    fn execute(&self, program: &str, args: &[&str]) -> Result<()> {
+        self.record(program, args)
+    }
+
+    fn capture(&self, program: &str, args: &[&str]) -> Result<CommandOutput> {
+        self.record(program, args)?;
+        Ok(self.next_output())
+    }
+
+    fn stream(
+        &self,
+        program: &str,
+        args: &[&str],
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<()> {
+        self.record(program, args)?;
+        let output = self.next_output();
+
+        let lines = [
+            (OutputStream::Stdout, &output.stdout),
+            (OutputStream::Stderr, &output.stderr),
+        ];
+        for (stream, text) in lines {
+            for line in text.lines() {
+                on_line(OutputLine {
+                    stream,
+                    text: line.to_string(),
+                });
+            }
+        }
+
+        if output.success() {
+            Ok(())
+        } else {
+            Err(ShoalError::CommandFailed {
+                command: program.to_string(),
+                code: output.code,
+            }
+            .into())
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct MockFileSystem {
     pub files: Arc<std::sync::Mutex<HashMap<PathBuf, String>>>,
     pub directories: Arc<std::sync::Mutex<HashMap<PathBuf, Vec<PathBuf>>>>,
@@ -46,6 +122,21 @@ impl MockFileSystem {
             directories: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
     }
+
+    pub fn add_file(&self, dir: &str, name: &str, contents: &str) {
+        let dir = PathBuf::from(dir);
+        let path = dir.join(name);
+        self.directories
+            .lock()
+            .unwrap()
+            .entry(dir)
+            .or_default()
+            .push(path.clone());
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path, contents.to_string());
+    }
 }
 
 impl FileSystem for MockFileSystem {
@@ -85,6 +176,7 @@ impl FileSystem for MockFileSystem {
     }
 }
 
+#[derive(Clone)]
 pub struct MockPathProvider {
     pub current_dir: PathBuf,
     pub home_dir: PathBuf,
@@ -101,6 +193,12 @@ impl MockPathProvider {
     }
 }
 
+impl Default for MockPathProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PathProvider for MockPathProvider {
     fn current_dir(&self) -> Result<PathBuf> {
         Ok(self.current_dir.clone())
@@ -2,10 +2,10 @@ pub mod command;
 pub mod filesystem;
 pub mod path;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-util"))]
 pub mod mocks;
 
-pub use command::CommandExecutor;
+pub use command::{CommandExecutor, CommandOutput, OutputLine, OutputStream};
 pub use filesystem::FileSystem;
 pub use path::PathProvider;
 
@@ -7,6 +7,7 @@ pub trait PathProvider: Send + Sync {
     fn data_local_dir(&self) -> Result<PathBuf>;
 }
 
+#[derive(Clone, Copy, Debug, Default)]
 pub struct StdPathProvider;
 
 impl PathProvider for StdPathProvider {
@@ -0,0 +1,36 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileScope {
+    Local,
+    Global,
+}
+
+impl fmt::Display for FileScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileScope::Local => write!(f, "local"),
+            FileScope::Global => write!(f, "global"),
+        }
+    }
+}
+
+/// The file a stack, service or override definition was loaded from.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ConfigSource {
+    pub scope: FileScope,
+    pub path: PathBuf,
+}
+
+/// A loaded definition as shown by `shoal list`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigEntry {
+    pub name: String,
+    pub description: String,
+    #[serde(flatten)]
+    pub source: Option<ConfigSource>,
+}
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::types::docker_network::DockerNetwork;
 
@@ -10,17 +11,37 @@ pub struct DockerComposeFile {
     pub networks: HashMap<String, Option<DockerNetwork>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl DockerComposeFile {
+    /// Services that depend on `service`, directly or transitively, sorted by name.
+    pub fn dependents_of(&self, service: &str) -> Vec<String> {
+        let mut dependents: Vec<String> = Vec::new();
+        let mut pending = vec![service.to_string()];
+        while let Some(current) = pending.pop() {
+            for (name, docker_service) in &self.services {
+                let depends_on_current = docker_service
+                    .depends_on
+                    .as_ref()
+                    .is_some_and(|depends_on| depends_on.contains_key(&current));
+                if depends_on_current && name != service && !dependents.contains(name) {
+                    dependents.push(name.clone());
+                    pending.push(name.clone());
+                }
+            }
+        }
+
+        dependents.sort();
+        dependents
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DockerService {
     pub container_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub build_context: Option<String>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub dockerfile: Option<String>,
+    pub build: Option<DockerBuild>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub entrypoint: Option<Vec<String>>,
@@ -31,6 +52,9 @@ pub struct DockerService {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub environment: Option<HashMap<String, String>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Vec<String>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ports: Option<Vec<String>>,
 
@@ -38,11 +62,206 @@ pub struct DockerService {
     pub volumes: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub depends_on: Option<Vec<String>>,
+    pub depends_on: Option<HashMap<String, DependsOn>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<HealthCheck>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub networks: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub restart: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+
+    /// Compose keys Shoal does not model, passed through to the generated file unchanged.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl DockerService {
+    /// Sets env vars, merging into a compose `environment` kept in `extra`.
+    pub fn merge_environment(&mut self, env: &HashMap<String, String>) {
+        let mut keys: Vec<&String> = env.keys().collect();
+        keys.sort();
+        match self.extra.get_mut("environment") {
+            Some(Value::Object(variables)) => {
+                for key in keys {
+                    variables.insert(key.clone(), Value::String(env[key].clone()));
+                }
+            }
+            Some(Value::Array(entries)) => {
+                entries.retain(|entry| env_entry_key(entry).is_none_or(|k| !env.contains_key(k)));
+                entries.extend(
+                    keys.into_iter()
+                        .map(|key| Value::String(format!("{key}={}", env[key]))),
+                );
+            }
+            _ => {
+                self.extra.remove("environment");
+                self.environment
+                    .get_or_insert_with(HashMap::new)
+                    .extend(env.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+        }
+    }
+
+    /// Removes an env var, returning whether it was set.
+    pub fn unset_environment(&mut self, key: &str) -> bool {
+        let mut removed = self
+            .environment
+            .as_mut()
+            .is_some_and(|env| env.remove(key).is_some());
+        if self.environment.as_ref().is_some_and(HashMap::is_empty) {
+            self.environment = None;
+        }
+
+        match self.extra.get_mut("environment") {
+            Some(Value::Object(variables)) => removed |= variables.remove(key).is_some(),
+            Some(Value::Array(entries)) => {
+                let before = entries.len();
+                entries.retain(|entry| env_entry_key(entry) != Some(key));
+                removed |= entries.len() != before;
+            }
+            _ => {}
+        }
+        removed
+    }
+
+    /// Drops every env var, in either form.
+    pub fn clear_environment(&mut self) {
+        self.environment = None;
+        self.extra.remove("environment");
+    }
+
+    /// Appends to a list kept in `extra`, returning false when there is none.
+    pub fn append_passthrough(&mut self, key: &str, entries: &[String]) -> bool {
+        if let Some(single @ Value::String(_)) = self.extra.get(key) {
+            let list = Value::Array(vec![single.clone()]);
+            self.extra.insert(key.to_string(), list);
+        }
+        let Some(Value::Array(existing)) = self.extra.get_mut(key) else {
+            return false;
+        };
+        existing.extend(entries.iter().cloned().map(Value::String));
+        true
+    }
+}
+
+fn env_entry_key(entry: &Value) -> Option<&str> {
+    let entry = entry.as_str()?;
+    Some(entry.split_once('=').map_or(entry, |(key, _)| key))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DockerBuild {
+    pub context: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dockerfile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DependsOn {
+    pub condition: DependencyCondition,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DependencyCondition {
+    #[serde(rename = "service_started")]
+    Started,
+    #[serde(rename = "service_healthy")]
+    Healthy,
+    #[serde(rename = "service_completed_successfully")]
+    CompletedSuccessfully,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HealthCheck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test: Option<HealthCheckTest>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_period: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_interval: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+impl HealthCheck {
+    pub fn is_enabled(&self) -> bool {
+        let test_disabled = match &self.test {
+            Some(HealthCheckTest::Exec(test)) => test.first().is_some_and(|t| t == "NONE"),
+            _ => false,
+        };
+        !self.disable.unwrap_or(false) && !test_disabled
+    }
+}
+
+/// Either a shell command string or an exec-form list such as `["CMD", "pg_isready"]`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum HealthCheckTest {
+    Shell(String),
+    Exec(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service_depending_on(dependencies: &[&str]) -> DockerService {
+        DockerService {
+            depends_on: Some(
+                dependencies
+                    .iter()
+                    .map(|d| {
+                        (
+                            d.to_string(),
+                            DependsOn {
+                                condition: DependencyCondition::Started,
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dependents_of_is_transitive() {
+        let mut services = HashMap::new();
+        services.insert("postgres".to_string(), DockerService::default());
+        services.insert("backend".to_string(), service_depending_on(&["postgres"]));
+        services.insert("worker".to_string(), service_depending_on(&["postgres"]));
+        services.insert("frontend".to_string(), service_depending_on(&["backend"]));
+        services.insert("docs".to_string(), DockerService::default());
+
+        let compose = DockerComposeFile {
+            services,
+            networks: HashMap::new(),
+        };
+
+        assert_eq!(
+            compose.dependents_of("postgres"),
+            vec!["backend", "frontend", "worker"]
+        );
+        assert!(compose.dependents_of("frontend").is_empty());
+    }
 }
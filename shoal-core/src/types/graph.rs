@@ -0,0 +1,43 @@
+use serde::Serialize;
+
+/// The services a stack starts, as resolved for `shoal graph`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceGraph {
+    pub stack: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNode {
+    pub name: String,
+    pub role: NodeRole,
+    pub source: NodeSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    /// Listed directly in the stack.
+    Root,
+    /// Started because something in the stack depends on it.
+    Dependency,
+    /// Reachable from the stack but not started because of its `exclude` list.
+    Excluded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeSource {
+    Image,
+    Local,
+}
+
+/// `from` depends on `to`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    /// Set when either end is excluded from the stack.
+    pub excluded: bool,
+}
@@ -0,0 +1,21 @@
+use crate::traits::OutputStream;
+
+/// Options passed through to `docker compose logs`.
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    /// Services to show; every service in the stack when empty.
+    pub services: Vec<String>,
+    pub follow: bool,
+    /// Only show logs since a timestamp (`2024-01-01T10:00:00`) or relative time (`10m`).
+    pub since: Option<String>,
+    /// Number of lines to show from the end of each service's logs, or `all`.
+    pub tail: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    /// The service the line came from, or `None` for lines docker printed itself.
+    pub service: Option<String>,
+    pub message: String,
+    pub stream: OutputStream,
+}
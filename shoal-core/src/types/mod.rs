@@ -1,5 +1,11 @@
+pub mod config_source;
 pub mod docker_network;
 pub mod docker_service;
+pub mod graph;
+pub mod logs;
+pub mod provenance;
 pub mod service;
 pub mod stack;
 pub mod stack_override;
+pub mod stack_status;
+pub mod validation;
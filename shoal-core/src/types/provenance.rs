@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::types::config_source::ConfigSource;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DefinitionKind {
+    Stack,
+    Service,
+    Override,
+}
+
+impl fmt::Display for DefinitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefinitionKind::Stack => write!(f, "stack"),
+            DefinitionKind::Service => write!(f, "service"),
+            DefinitionKind::Override => write!(f, "override"),
+        }
+    }
+}
+
+/// Where a stack, service or override is defined. Overrides are named `stack.override`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Definition {
+    pub kind: DefinitionKind,
+    pub name: String,
+    /// The file whose definition is used.
+    pub active: Option<ConfigSource>,
+    /// Definitions with the same name that `active` shadows, in load order.
+    pub shadowed: Vec<ConfigSource>,
+}
+
+/// The layer of configuration that set a field on a generated service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldLayer {
+    Service,
+    Stack,
+    Override,
+}
+
+impl fmt::Display for FieldLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldLayer::Service => write!(f, "service"),
+            FieldLayer::Stack => write!(f, "stack"),
+            FieldLayer::Override => write!(f, "override"),
+        }
+    }
+}
+
+/// A field of a generated service and the file that set it.
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldOrigin {
+    pub field: &'static str,
+    pub value: String,
+    pub layer: FieldLayer,
+    pub source: Option<ConfigSource>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StackProvenance {
+    pub stack: String,
+    pub services: BTreeMap<String, Vec<FieldOrigin>>,
+}
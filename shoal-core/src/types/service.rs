@@ -1,8 +1,12 @@
+use std::collections::HashMap;
 use std::fmt;
+use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+use crate::types::{config_source::ConfigSource, docker_service::HealthCheck};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 pub enum LocationType {
     Image,
     Local,
@@ -21,10 +25,12 @@ impl fmt::Display for LocationType {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ServiceLocation {
     pub r#type: LocationType,
     pub location: String,
+    #[serde(default)]
+    pub dockerfile: Option<String>,
 }
 
 impl fmt::Display for ServiceLocation {
@@ -33,19 +39,80 @@ impl fmt::Display for ServiceLocation {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct Service {
     pub service_name: String,
-    pub source: ServiceLocation,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub source: Option<ServiceLocation>,
+    #[serde(default)]
     pub internal_ports: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub env_file: Vec<String>,
+    #[serde(default)]
+    pub volumes: Vec<String>,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub entrypoint: Option<Vec<String>>,
+    #[serde(default)]
+    pub restart: Option<String>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub healthcheck: Option<HealthCheck>,
+    /// Labels that overrides can target with `tag:<label>`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A raw Docker Compose service body, used as the base of the generated service.
+    #[serde(default)]
+    pub compose: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(skip)]
+    pub origin: Option<ConfigSource>,
+    /// Definitions with the same name this one shadows, in load order.
+    #[serde(skip)]
+    pub shadowed: Vec<ConfigSource>,
+}
+
+impl Service {
+    /// Resolves a path from the service definition relative to the file it was loaded from.
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        match self.origin.as_ref().and_then(|o| o.path.parent()) {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Resolves the host side of a relative bind mount.
+    pub fn resolve_volume(&self, volume: &str) -> String {
+        match volume.split_once(':') {
+            Some((host, container)) if host.starts_with('.') => {
+                format!("{}:{}", self.resolve_path(host).display(), container)
+            }
+            _ => volume.to_string(),
+        }
+    }
 }
 
 impl fmt::Display for Service {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{} [{}] ports: {:?}",
-            self.service_name, self.source, self.internal_ports
-        )
+        match &self.source {
+            Some(source) => write!(
+                f,
+                "{} [{}] ports: {:?}",
+                self.service_name, source, self.internal_ports
+            ),
+            None => write!(
+                f,
+                "{} [Compose] ports: {:?}",
+                self.service_name, self.internal_ports
+            ),
+        }
     }
 }
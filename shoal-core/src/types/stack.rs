@@ -1,8 +1,22 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+use crate::types::{config_source::ConfigSource, stack_override::Override};
+
+#[derive(Clone, Deserialize)]
 pub struct Stack {
     pub name: String,
     pub description: String,
     pub services: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Overrides that are always applied when the stack runs.
+    #[serde(default)]
+    pub overrides: HashMap<String, Override>,
+    #[serde(skip)]
+    pub origin: Option<ConfigSource>,
+    /// Definitions with the same name this one shadows, in load order.
+    #[serde(skip)]
+    pub shadowed: Vec<ConfigSource>,
 }
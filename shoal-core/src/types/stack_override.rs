@@ -2,19 +2,108 @@ use std::collections::HashMap;
 
 use serde::Deserialize;
 
+use crate::types::{
+    config_source::{ConfigSource, FileScope},
+    service::LocationType,
+};
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct StackOverride {
     pub name: String,
-    pub stack: String,
-    _description: String,
+    /// The stack this override is for. Without one it can be applied to any stack.
+    #[serde(default)]
+    pub stack: Option<String>,
+    #[serde(default, alias = "_description")]
+    pub description: String,
+    #[serde(default)]
     pub overrides: HashMap<String, Override>,
+    /// Applies the override without it being named. Only honoured in `~/.shoal/overrides`.
+    #[serde(default)]
+    pub auto_apply: bool,
+    /// Services started alongside the stack's own, with their dependencies.
+    #[serde(default)]
+    pub add_services: Vec<String>,
+    /// Services left out of the stack, along with anything only they depend on.
+    #[serde(default)]
+    pub remove_services: Vec<String>,
+    #[serde(skip)]
+    pub origin: Option<ConfigSource>,
+    /// Definitions with the same name this one shadows, in load order.
+    #[serde(skip)]
+    pub shadowed: Vec<ConfigSource>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl StackOverride {
+    /// The key an override is loaded under, `*-{name}` for any stack.
+    pub fn key_for(stack: Option<&str>, name: &str) -> String {
+        format!("{}-{name}", stack.unwrap_or("*"))
+    }
+
+    pub fn key(&self) -> String {
+        Self::key_for(self.stack.as_deref(), &self.name)
+    }
+
+    /// How the override is named on the command line: `stack.name`, or just `name`.
+    pub fn label(&self) -> String {
+        match &self.stack {
+            Some(stack) => format!("{stack}.{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Whether the override is applied to `stack` without being named.
+    pub fn auto_applies_to(&self, stack: &str) -> bool {
+        self.auto_apply
+            && self.stack.as_deref().is_none_or(|s| s == stack)
+            && self
+                .origin
+                .as_ref()
+                .is_some_and(|origin| origin.scope == FileScope::Global)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Override {
     pub env: Option<HashMap<String, String>>,
     pub ports: Option<Vec<String>>,
     pub command: Option<Vec<String>>,
     pub entrypoint: Option<Vec<String>>,
     pub volumes: Option<Vec<String>>,
+    /// Changes where the service comes from, e.g. to build it from a local checkout.
+    #[serde(default)]
+    pub source: Option<SourceOverride>,
+    /// Env vars to remove from the service.
+    #[serde(default)]
+    pub unset_env: Vec<String>,
+    /// Ports to remove, matched by the whole mapping or by container port.
+    #[serde(default)]
+    pub remove_ports: Vec<String>,
+    /// Volumes to remove, matched by the whole entry or by container path.
+    #[serde(default)]
+    pub remove_volumes: Vec<String>,
+    /// Fields whose inherited values are replaced rather than merged.
+    #[serde(default)]
+    pub replace: Vec<ReplaceField>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplaceField {
+    Env,
+    Ports,
+    Volumes,
+}
+
+/// Changes to a service's source; unset fields keep the service's own.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SourceOverride {
+    #[serde(default)]
+    pub r#type: Option<LocationType>,
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Replaces the tag of an image source.
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub dockerfile: Option<String>,
 }
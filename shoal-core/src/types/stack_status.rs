@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+/// A container as reported by `docker compose ps --format json`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerStatus {
+    pub name: String,
+    pub service: String,
+    pub state: String,
+    #[serde(default)]
+    pub health: String,
+    #[serde(default)]
+    pub running_for: String,
+    #[serde(default)]
+    pub publishers: Option<Vec<PortPublisher>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct PortPublisher {
+    #[serde(default)]
+    pub target_port: u16,
+    #[serde(default)]
+    pub published_port: u16,
+    #[serde(default)]
+    pub protocol: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StackState {
+    Up,
+    Degraded,
+    Down,
+}
+
+impl std::fmt::Display for StackState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StackState::Up => write!(f, "up"),
+            StackState::Degraded => write!(f, "degraded"),
+            StackState::Down => write!(f, "down"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ServiceStatus {
+    pub service: String,
+    pub container: Option<String>,
+    pub state: String,
+    pub health: Option<String>,
+    pub ports: Vec<String>,
+    pub uptime: Option<String>,
+    /// Set when the service is part of the stack definition but has no container.
+    pub missing: bool,
+}
+
+impl ServiceStatus {
+    pub fn is_ready(&self) -> bool {
+        self.state == "running" && self.health.as_deref().is_none_or(|h| h == "healthy")
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StackStatus {
+    pub stack: String,
+    pub state: StackState,
+    pub services: Vec<ServiceStatus>,
+}
+
+impl StackStatus {
+    /// Joins the services a stack should run with the containers docker reports for it.
+    pub fn from_containers(
+        stack: impl Into<String>,
+        expected_services: &[String],
+        containers: Vec<ContainerStatus>,
+    ) -> Self {
+        let mut services: Vec<ServiceStatus> = expected_services
+            .iter()
+            .map(
+                |service| match containers.iter().find(|c| &c.service == service) {
+                    Some(container) => ServiceStatus::from(container),
+                    None => ServiceStatus {
+                        service: service.clone(),
+                        container: None,
+                        state: "missing".to_string(),
+                        health: None,
+                        ports: vec![],
+                        uptime: None,
+                        missing: true,
+                    },
+                },
+            )
+            .collect();
+
+        services.extend(
+            containers
+                .iter()
+                .filter(|c| !expected_services.contains(&c.service))
+                .map(ServiceStatus::from),
+        );
+
+        let ready = services.iter().filter(|s| s.is_ready()).count();
+        let state = if ready == 0 {
+            StackState::Down
+        } else if ready == services.len() {
+            StackState::Up
+        } else {
+            StackState::Degraded
+        };
+
+        StackStatus {
+            stack: stack.into(),
+            state,
+            services,
+        }
+    }
+}
+
+impl From<&ContainerStatus> for ServiceStatus {
+    fn from(container: &ContainerStatus) -> Self {
+        let mut ports: Vec<String> = container
+            .publishers
+            .iter()
+            .flatten()
+            .filter(|p| p.published_port != 0)
+            .map(|p| format!("{}->{}/{}", p.published_port, p.target_port, p.protocol))
+            .collect();
+        ports.dedup();
+
+        let running = container.state == "running";
+        ServiceStatus {
+            service: container.service.clone(),
+            container: Some(container.name.clone()),
+            state: container.state.clone(),
+            health: (!container.health.is_empty()).then(|| container.health.clone()),
+            ports,
+            uptime: (running && !container.running_for.is_empty())
+                .then(|| container.running_for.trim_end_matches(" ago").to_string()),
+            missing: false,
+        }
+    }
+}
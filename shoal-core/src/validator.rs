@@ -0,0 +1,1089 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{
+    compose::ComposeFileManager,
+    config::loader::{ConfigFile, ConfigLoader},
+    docker::service::detect_dockerfile,
+    error::ShoalError,
+    override_handler::{
+        apply_service_changes, apply_source_override, is_selector, matches_selector,
+    },
+    resolver::resolve_dependencies,
+    stack::StackManager,
+    traits::{CommandExecutor, FileSystem, PathProvider},
+    types::{
+        config_source::FileScope,
+        docker_service::DockerComposeFile,
+        service::{LocationType, Service},
+        stack::Stack,
+        stack_override::{Override, SourceOverride, StackOverride},
+        validation::{Finding, Severity, ValidationReport},
+    },
+};
+
+/// Checks every stack, service and override file without starting anything.
+pub struct Validator<FS: FileSystem, PP: PathProvider> {
+    file_system: FS,
+    path_provider: PP,
+    command_executor: Arc<dyn CommandExecutor>,
+}
+
+impl<FS: FileSystem + Clone, PP: PathProvider + Clone> Validator<FS, PP> {
+    pub fn new(
+        file_system: FS,
+        path_provider: PP,
+        command_executor: Arc<dyn CommandExecutor>,
+    ) -> Self {
+        Self {
+            file_system,
+            path_provider,
+            command_executor,
+        }
+    }
+
+    pub fn validate(&self) -> Result<ValidationReport> {
+        let loader = ConfigLoader::new(self.file_system.clone(), self.path_provider.clone());
+        let mut findings = Findings::default();
+
+        let all_services = findings.collect(loader.service_files()?, "Service", |service| {
+            service.service_name.clone()
+        });
+        let all_stacks =
+            findings.collect(loader.stack_files()?, "Stack", |stack| stack.name.clone());
+        let all_overrides = findings.collect(loader.override_files()?, "Override", |o| o.label());
+
+        let services = by_name(&all_services, |service| service.service_name.clone());
+        let stacks = by_name(&all_stacks, |stack| stack.name.clone());
+        let overrides = by_name(&all_overrides, |o| o.key());
+
+        for service in &all_services {
+            self.check_service(&mut findings, service, &services);
+        }
+
+        let mut cycles = HashSet::new();
+        let mut service_names: Vec<&String> = services.keys().collect();
+        service_names.sort();
+        for service_name in service_names {
+            let roots = [service_name.clone()];
+            if let Err(error) = resolve_dependencies(&roots, &[], &services)
+                && let Some(ShoalError::DependencyCycle { path: cycle }) = error.downcast_ref()
+            {
+                report_cycle(&mut findings, cycle, &services, &mut cycles);
+            }
+        }
+
+        let mut resolved_stacks = HashMap::new();
+        for stack in &all_stacks {
+            self.check_override_sources(
+                &mut findings,
+                &stack.overrides,
+                stack.origin.as_ref().map(|o| o.path.as_path()),
+                &services,
+            );
+            let resolved = check_stack(&mut findings, stack, &services);
+            if let Some(resolved) = resolved
+                && stacks
+                    .get(&stack.name)
+                    .is_some_and(|s| s.origin == stack.origin)
+            {
+                resolved_stacks.insert(stack.name.clone(), resolved);
+            }
+        }
+
+        for stack_override in &all_overrides {
+            check_override(
+                &mut findings,
+                stack_override,
+                &stacks,
+                &services,
+                &resolved_stacks,
+            );
+            self.check_override_sources(
+                &mut findings,
+                &stack_override.overrides,
+                stack_override.origin.as_ref().map(|o| o.path.as_path()),
+                &services,
+            );
+        }
+
+        let stack_manager = StackManager::new(
+            services.clone(),
+            stacks,
+            overrides.clone(),
+            ComposeFileManager::new(self.file_system.clone(), self.path_provider.clone()),
+            self.command_executor.clone(),
+        );
+        let mut collisions = HashSet::new();
+        let mut stack_names: Vec<&String> = resolved_stacks.keys().collect();
+        stack_names.sort();
+        for stack_name in stack_names {
+            let stack_path = stack_manager.stacks()[stack_name]
+                .origin
+                .as_ref()
+                .map(|o| o.path.clone());
+            let mut stack_overrides: Vec<&StackOverride> = overrides
+                .values()
+                .filter(|o| o.stack.as_ref() == Some(stack_name))
+                .collect();
+            stack_overrides.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let mut variants = vec![(stack_name.clone(), stack_path.clone())];
+            variants.extend(stack_overrides.into_iter().map(|o| {
+                (
+                    format!("{stack_name}.{}", o.name),
+                    o.origin.as_ref().map(|origin| origin.path.clone()),
+                )
+            }));
+
+            for (variant, variant_path) in variants {
+                // Anything that stops the compose file being built is reported by the
+                // checks above.
+                let Ok((_, compose, _)) = stack_manager.build_compose(&variant) else {
+                    continue;
+                };
+                let fallback_paths: Vec<PathBuf> = [variant_path, stack_path.clone()]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                check_ports(
+                    &mut findings,
+                    &variant,
+                    &compose,
+                    &services,
+                    &fallback_paths,
+                    &mut collisions,
+                );
+            }
+        }
+
+        Ok(ValidationReport {
+            findings: findings.findings,
+        })
+    }
+
+    fn check_service(
+        &self,
+        findings: &mut Findings,
+        service: &Service,
+        services: &HashMap<String, Service>,
+    ) {
+        let Some(path) = service.origin.as_ref().map(|o| o.path.clone()) else {
+            return;
+        };
+
+        for dependency in &service.dependencies {
+            if !services.contains_key(dependency) {
+                let line = findings.line_of(&path, Some("dependencies"), dependency);
+                findings.push(
+                    Severity::Error,
+                    format!(
+                        "Service '{}' depends on unknown service '{dependency}'",
+                        service.service_name
+                    ),
+                    &path,
+                    line,
+                );
+            }
+        }
+
+        self.check_local_source(findings, service, &path);
+    }
+
+    fn check_local_source(&self, findings: &mut Findings, service: &Service, path: &Path) {
+        let Some(source) = &service.source else {
+            return;
+        };
+        if !matches!(source.r#type, LocationType::Local) {
+            return;
+        }
+
+        let context = service.resolve_path(&source.location);
+        if !self.file_system.exists(&context) {
+            let line = findings.key_line(path, "location");
+            findings.push(
+                Severity::Error,
+                format!("Local source path {} does not exist", context.display()),
+                path,
+                line,
+            );
+            return;
+        }
+
+        let exists = |path: &Path| self.file_system.exists(path);
+        match &source.dockerfile {
+            Some(dockerfile) if !exists(&context.join(dockerfile)) => {
+                let line = findings.key_line(path, "dockerfile");
+                findings.push(
+                    Severity::Error,
+                    format!(
+                        "Dockerfile {} does not exist",
+                        context.join(dockerfile).display()
+                    ),
+                    path,
+                    line,
+                );
+            }
+            None if !exists(&context.join("Dockerfile"))
+                && detect_dockerfile(&context, exists).is_none() =>
+            {
+                let line = findings.key_line(path, "location");
+                findings.push(
+                    Severity::Error,
+                    format!("No Dockerfile found in {}", context.display()),
+                    path,
+                    line,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn check_override_sources(
+        &self,
+        findings: &mut Findings,
+        overrides: &HashMap<String, Override>,
+        path: Option<&Path>,
+        services: &HashMap<String, Service>,
+    ) {
+        let Some(path) = path else {
+            return;
+        };
+
+        let mut targets: Vec<(&String, &SourceOverride)> = overrides
+            .iter()
+            .filter_map(|(name, o)| o.source.as_ref().map(|source| (name, source)))
+            .collect();
+        targets.sort_by_key(|(name, _)| *name);
+        for (target, source_override) in targets {
+            let mut matched: Vec<&Service> = services
+                .values()
+                .filter(|service| matches_selector(target, service))
+                .collect();
+            matched.sort_by_key(|service| &service.service_name);
+            for service in matched {
+                let mut service = service.clone();
+                match apply_source_override(&mut service, source_override, path.parent()) {
+                    // A service's own local source is checked against its own file.
+                    Ok(())
+                        if source_override.location.is_none()
+                            && source_override.dockerfile.is_none() => {}
+                    Ok(()) => self.check_local_source(findings, &service, path),
+                    Err(error) => {
+                        let line = findings.line_of(path, Some("overrides"), target);
+                        findings.push(Severity::Error, error.to_string(), path, line);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn check_stack(
+    findings: &mut Findings,
+    stack: &Stack,
+    services: &HashMap<String, Service>,
+) -> Option<Vec<String>> {
+    let path = stack.origin.as_ref()?.path.clone();
+
+    let mut unknown = false;
+    for service in &stack.services {
+        if !services.contains_key(service) {
+            unknown = true;
+            let line = findings.line_of(&path, Some("services"), service);
+            findings.push(
+                Severity::Error,
+                format!(
+                    "Stack '{}' references unknown service '{service}'",
+                    stack.name
+                ),
+                &path,
+                line,
+            );
+        }
+    }
+    for service in &stack.exclude {
+        if !services.contains_key(service) {
+            let line = findings.line_of(&path, Some("exclude"), service);
+            findings.push(
+                Severity::Warning,
+                format!(
+                    "Stack '{}' excludes unknown service '{service}'",
+                    stack.name
+                ),
+                &path,
+                line,
+            );
+        }
+    }
+    if unknown {
+        return None;
+    }
+
+    // Unknown dependencies and cycles are reported against the services involved.
+    let resolved = resolve_dependencies(&stack.services, &stack.exclude, services).ok()?;
+
+    let mut targets: Vec<&String> = stack.overrides.keys().collect();
+    targets.sort();
+    for service in targets {
+        if is_selector(service) {
+            let matches_any = resolved
+                .iter()
+                .filter_map(|name| services.get(name))
+                .any(|s| matches_selector(service, s));
+            if !matches_any {
+                let line = findings.line_of(&path, Some("overrides"), service);
+                findings.push(
+                    Severity::Warning,
+                    format!(
+                        "Stack '{}' override target '{service}' matches no service in the stack",
+                        stack.name
+                    ),
+                    &path,
+                    line,
+                );
+            }
+        } else if !resolved.contains(service) {
+            let line = findings.line_of(&path, Some("overrides"), service);
+            findings.push(
+                Severity::Error,
+                format!(
+                    "Stack '{}' overrides service '{service}', which is not in the stack",
+                    stack.name
+                ),
+                &path,
+                line,
+            );
+        }
+    }
+
+    Some(resolved)
+}
+
+fn report_cycle(
+    findings: &mut Findings,
+    cycle: &[String],
+    services: &HashMap<String, Service>,
+    cycles: &mut HashSet<Vec<String>>,
+) {
+    let mut key = cycle.to_vec();
+    key.sort();
+    key.dedup();
+    if !cycles.insert(key) {
+        return;
+    }
+
+    let Some(path) = cycle
+        .first()
+        .and_then(|service| services.get(service))
+        .and_then(|service| service.origin.as_ref())
+        .map(|origin| origin.path.clone())
+    else {
+        return;
+    };
+    let line = cycle
+        .get(1)
+        .and_then(|next| findings.line_of(&path, Some("dependencies"), next));
+    findings.push(
+        Severity::Error,
+        format!("Dependency cycle: {}", cycle.join(" -> ")),
+        &path,
+        line,
+    );
+}
+
+fn check_override(
+    findings: &mut Findings,
+    stack_override: &StackOverride,
+    stacks: &HashMap<String, Stack>,
+    services: &HashMap<String, Service>,
+    resolved_stacks: &HashMap<String, Vec<String>>,
+) {
+    let Some(origin) = stack_override.origin.as_ref() else {
+        return;
+    };
+    let path = origin.path.clone();
+
+    if stack_override.auto_apply && origin.scope != FileScope::Global {
+        let line = findings.key_line(&path, "auto_apply");
+        findings.push(
+            Severity::Warning,
+            format!(
+                "Override '{}' sets auto_apply, which only applies to overrides in ~/.shoal/overrides",
+                stack_override.name
+            ),
+            &path,
+            line,
+        );
+    }
+
+    if let Some(stack_name) = &stack_override.stack
+        && !stacks.contains_key(stack_name)
+    {
+        let line = findings.key_line(&path, "stack");
+        findings.push(
+            Severity::Error,
+            format!(
+                "Override '{}' targets unknown stack '{stack_name}'",
+                stack_override.name
+            ),
+            &path,
+            line,
+        );
+        return;
+    }
+
+    let mut unknown_added = false;
+    for service in unknown_services(&stack_override.add_services, services) {
+        unknown_added = true;
+        let line = findings.line_of(&path, Some("add_services"), service);
+        findings.push(
+            Severity::Error,
+            format!(
+                "Override '{}' adds unknown service '{service}'",
+                stack_override.name
+            ),
+            &path,
+            line,
+        );
+    }
+    for service in unknown_services(&stack_override.remove_services, services) {
+        let line = findings.line_of(&path, Some("remove_services"), service);
+        findings.push(
+            Severity::Warning,
+            format!(
+                "Override '{}' removes unknown service '{service}'",
+                stack_override.name
+            ),
+            &path,
+            line,
+        );
+    }
+
+    let Some(stack_name) = &stack_override.stack else {
+        check_any_stack_targets(findings, stack_override, services, &path);
+        return;
+    };
+    let Some(stack_services) = resolved_stacks.get(stack_name) else {
+        return;
+    };
+    for service in stack_override
+        .remove_services
+        .iter()
+        .filter(|s| services.contains_key(*s) && !stack_services.contains(s))
+    {
+        let line = findings.line_of(&path, Some("remove_services"), service);
+        findings.push(
+            Severity::Warning,
+            format!(
+                "Override '{}' removes service '{service}', which is not in stack '{stack_name}'",
+                stack_override.name
+            ),
+            &path,
+            line,
+        );
+    }
+    if unknown_added {
+        return;
+    }
+
+    // The override can target the services it adds, but not the ones it removes.
+    let stack = apply_service_changes(&stacks[stack_name], &[stack_override]);
+    let Ok(resolved) = resolve_dependencies(&stack.services, &stack.exclude, services) else {
+        return;
+    };
+
+    let mut targets: Vec<&String> = stack_override.overrides.keys().collect();
+    targets.sort();
+    for service in targets {
+        if is_selector(service) {
+            let matches_any = resolved
+                .iter()
+                .filter_map(|name| services.get(name))
+                .any(|s| matches_selector(service, s));
+            if !matches_any {
+                let line = findings.line_of(&path, Some("overrides"), service);
+                findings.push(
+                    Severity::Warning,
+                    format!(
+                        "Override '{}' target '{service}' matches no service in stack '{stack_name}'",
+                        stack_override.name
+                    ),
+                    &path,
+                    line,
+                );
+            }
+        } else if !resolved.contains(service) {
+            let line = findings.line_of(&path, Some("overrides"), service);
+            findings.push(
+                Severity::Error,
+                format!(
+                    "Override '{}' targets service '{service}', which is not in stack '{stack_name}'",
+                    stack_override.name
+                ),
+                &path,
+                line,
+            );
+        }
+    }
+}
+
+fn check_any_stack_targets(
+    findings: &mut Findings,
+    stack_override: &StackOverride,
+    services: &HashMap<String, Service>,
+    path: &Path,
+) {
+    let mut targets: Vec<&String> = stack_override.overrides.keys().collect();
+    targets.sort();
+    for target in targets {
+        if services.values().any(|s| matches_selector(target, s)) {
+            continue;
+        }
+        let line = findings.line_of(path, Some("overrides"), target);
+        findings.push(
+            Severity::Warning,
+            format!(
+                "Override '{}' target '{target}' matches no service",
+                stack_override.name
+            ),
+            path,
+            line,
+        );
+    }
+}
+
+fn unknown_services<'a>(
+    names: &'a [String],
+    services: &'a HashMap<String, Service>,
+) -> impl Iterator<Item = &'a String> {
+    names.iter().filter(|name| !services.contains_key(*name))
+}
+
+fn check_ports(
+    findings: &mut Findings,
+    stack_label: &str,
+    compose: &DockerComposeFile,
+    services: &HashMap<String, Service>,
+    fallback_paths: &[PathBuf],
+    collisions: &mut HashSet<(String, String, String)>,
+) {
+    let mut service_names: Vec<&String> = compose.services.keys().collect();
+    service_names.sort();
+
+    let mut published: HashMap<String, &String> = HashMap::new();
+    for service_name in service_names {
+        let docker_service = &compose.services[service_name];
+        let short = docker_service
+            .ports
+            .iter()
+            .flatten()
+            .filter_map(|p| host_port(p));
+        let long = match docker_service.extra.get("ports") {
+            Some(Value::Array(ports)) => ports.iter().filter_map(long_host_port).collect(),
+            _ => Vec::new(),
+        };
+
+        for (port, display) in short.chain(long) {
+            let Some(first) = published.get(&port) else {
+                published.insert(port, service_name);
+                continue;
+            };
+            if !collisions.insert((port.clone(), (*first).clone(), service_name.clone())) {
+                continue;
+            }
+
+            let service_path = services
+                .get(service_name)
+                .and_then(|service| service.origin.as_ref())
+                .map(|origin| origin.path.clone());
+            let candidates: Vec<&PathBuf> = service_path.iter().chain(fallback_paths).collect();
+            let mut path = candidates.first().map(|path| (*path).clone());
+            let mut line = None;
+            for candidate in candidates {
+                if let Some(found) = findings.line_of(candidate, None, &display) {
+                    path = Some(candidate.clone());
+                    line = Some(found);
+                    break;
+                }
+            }
+            findings.findings.push(Finding {
+                severity: Severity::Error,
+                message: format!(
+                    "Host port {port} is published by both '{first}' and '{service_name}' in stack '{stack_label}'"
+                ),
+                path,
+                line,
+            });
+        }
+    }
+}
+
+fn host_port(mapping: &str) -> Option<(String, String)> {
+    let (mapping, protocol) = mapping.split_once('/').unwrap_or((mapping, "tcp"));
+    let parts: Vec<&str> = mapping.split(':').collect();
+    let host = match parts.as_slice() {
+        [host, _] | [_, host, _] if !host.is_empty() => *host,
+        _ => return None,
+    };
+    Some((format!("{host}/{protocol}"), host.to_string()))
+}
+
+fn long_host_port(mapping: &Value) -> Option<(String, String)> {
+    let published = match mapping.get("published")? {
+        Value::String(port) => port.clone(),
+        Value::Number(port) => port.to_string(),
+        _ => return None,
+    };
+    let protocol = mapping
+        .get("protocol")
+        .and_then(Value::as_str)
+        .unwrap_or("tcp");
+    Some((format!("{published}/{protocol}"), published))
+}
+
+fn by_name<T: Clone>(items: &[T], name: impl Fn(&T) -> String) -> HashMap<String, T> {
+    // Later files shadow earlier ones, matching how the loader resolves names.
+    items
+        .iter()
+        .map(|item| (name(item), item.clone()))
+        .collect()
+}
+
+#[derive(Default)]
+struct Findings {
+    contents: HashMap<PathBuf, String>,
+    findings: Vec<Finding>,
+}
+
+impl Findings {
+    fn push(&mut self, severity: Severity, message: String, path: &Path, line: Option<usize>) {
+        self.findings.push(Finding {
+            severity,
+            message,
+            path: Some(path.to_path_buf()),
+            line,
+        });
+    }
+
+    fn collect<T>(
+        &mut self,
+        files: Vec<ConfigFile<T>>,
+        kind: &str,
+        name: impl Fn(&T) -> String,
+    ) -> Vec<T> {
+        let mut seen: HashMap<(String, FileScope), PathBuf> = HashMap::new();
+        let mut items = Vec::new();
+
+        for file in files {
+            let path = file.source.path.clone();
+            self.contents.insert(path.clone(), file.contents);
+
+            let item = match file.item {
+                Ok(item) => item,
+                Err(error) => {
+                    let message = match error.downcast_ref::<ShoalError>() {
+                        Some(ShoalError::ConfigParse { message, .. }) => message.clone(),
+                        _ => format!("{error:#}"),
+                    };
+                    let line = parse_error_line(&message);
+                    self.push(Severity::Error, message, &path, line);
+                    continue;
+                }
+            };
+
+            let item_name = name(&item);
+            let key = (item_name.clone(), file.source.scope);
+            if let Some(first) = seen.get(&key) {
+                let line = self.line_of(&path, None, &item_name);
+                self.push(
+                    Severity::Error,
+                    format!(
+                        "{kind} '{item_name}' is also defined in {}",
+                        first.display()
+                    ),
+                    &path,
+                    line,
+                );
+            } else {
+                seen.insert(key, path);
+            }
+            items.push(item);
+        }
+
+        items
+    }
+
+    fn key_line(&self, path: &Path, key: &str) -> Option<usize> {
+        let contents = self.contents.get(path)?;
+        contents
+            .lines()
+            .position(|line| line.trim_start().starts_with(&format!("{key}:")))
+            .map(|index| index + 1)
+    }
+
+    fn line_of(&self, path: &Path, section: Option<&str>, token: &str) -> Option<usize> {
+        let contents = self.contents.get(path)?;
+        let start = section
+            .and_then(|section| self.key_line(path, section))
+            .map_or(0, |line| line - 1);
+
+        contents
+            .lines()
+            .enumerate()
+            .skip(start)
+            .find(|(_, line)| contains_word(line, token))
+            .map(|(index, _)| index + 1)
+    }
+}
+
+fn contains_word(line: &str, token: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    line.match_indices(token).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + token.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+fn parse_error_line(message: &str) -> Option<usize> {
+    let (_, rest) = message.rsplit_once("at line ")?;
+    rest.split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::mocks::{MockCommandExecutor, MockFileSystem, MockPathProvider};
+
+    fn validate(file_system: MockFileSystem) -> ValidationReport {
+        Validator::new(
+            file_system,
+            MockPathProvider::new(),
+            Arc::new(MockCommandExecutor::new()),
+        )
+        .validate()
+        .unwrap()
+    }
+
+    fn find<'a>(report: &'a ValidationReport, text: &str) -> &'a Finding {
+        report
+            .findings
+            .iter()
+            .find(|finding| finding.message.contains(text))
+            .unwrap_or_else(|| panic!("no finding containing '{text}' in {report:?}"))
+    }
+
+    #[test]
+    fn test_reports_unknown_references_with_lines() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file("/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api\ndependencies:\n  - ghost\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "full.yml",
+            "name: full\ndescription: Full\nservices:\n  - api\n  - nope\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "trace.yml",
+            "name: trace\nstack: missing\noverrides: {}\n",
+        );
+
+        let report = validate(file_system);
+        assert!(!report.is_valid());
+
+        let dependency = find(&report, "unknown service 'ghost'");
+        assert_eq!(
+            dependency.path,
+            Some(PathBuf::from("/test/current/services/api.yml"))
+        );
+        assert_eq!(dependency.line, Some(6));
+
+        assert_eq!(find(&report, "unknown service 'nope'").line, Some(5));
+        assert_eq!(find(&report, "unknown stack 'missing'").line, Some(2));
+    }
+
+    #[test]
+    fn test_override_service_changes() {
+        let file_system = MockFileSystem::new();
+        for name in ["api", "mailhog"] {
+            file_system.add_file(
+                "/test/current/services",
+                &format!("{name}.yml"),
+                &format!("service_name: {name}\nsource:\n  type: Image\n  location: {name}\n"),
+            );
+        }
+        file_system.add_file(
+            "/test/current/stacks",
+            "web.yml",
+            "name: web\ndescription: Web\nservices:\n  - api\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "mail.yml",
+            "name: mail\nstack: web\nadd_services:\n  - mailhog\nremove_services:\n  - ghost\n\
+             overrides:\n  mailhog:\n    env:\n      MH_UI: \"1\"\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "broken.yml",
+            "name: broken\nstack: web\nadd_services:\n  - missing\n",
+        );
+
+        let report = validate(file_system);
+
+        let unknown_added = find(&report, "adds unknown service 'missing'");
+        assert_eq!(unknown_added.severity, Severity::Error);
+        assert_eq!(unknown_added.line, Some(4));
+        let unknown_removed = find(&report, "removes unknown service 'ghost'");
+        assert_eq!(unknown_removed.severity, Severity::Warning);
+        assert_eq!(unknown_removed.line, Some(6));
+        // mailhog is added by the override itself, so it can be targeted.
+        assert!(
+            !report
+                .findings
+                .iter()
+                .any(|finding| finding.message.contains("targets service 'mailhog'"))
+        );
+    }
+
+    #[test]
+    fn test_overrides_for_any_stack() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api\ntags: [aws]\n",
+        );
+        file_system.add_file("/test/current/overrides",
+            "aws.yml",
+            "name: aws\nauto_apply: true\noverrides:\n  tag:aws:\n    volumes: [\"~/.aws:/root/.aws\"]\
+             \n  worker-*:\n    env:\n      AWS_PROFILE: worker\n",
+        );
+
+        let report = validate(file_system);
+
+        assert_eq!(find(&report, "sets auto_apply").line, Some(2));
+        assert_eq!(
+            find(&report, "target 'worker-*' matches no service").line,
+            Some(6)
+        );
+        assert!(
+            !report
+                .findings
+                .iter()
+                .any(|finding| finding.message.contains("tag:aws")
+                    || finding.message.contains("unknown stack"))
+        );
+    }
+
+    #[test]
+    fn test_selectors_in_stack_overrides() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api\ntags: [aws]\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "web.yml",
+            "name: web\ndescription: Web\nservices:\n  - api\noverrides:\n  tag:aws:\n    env:\
+             \n      AWS_PROFILE: web\n  worker-*:\n    env:\n      AWS_PROFILE: worker\n",
+        );
+
+        let report = validate(file_system);
+
+        let unmatched = find(&report, "target 'worker-*' matches no service");
+        assert_eq!(unmatched.severity, Severity::Warning);
+        assert_eq!(unmatched.line, Some(9));
+        assert!(
+            !report
+                .findings
+                .iter()
+                .any(|finding| finding.message.contains("tag:aws"))
+        );
+        assert!(report.is_valid());
+    }
+
+    #[test]
+    fn test_checks_sources_set_by_overrides() {
+        let file_system = MockFileSystem::new();
+        for name in ["api", "worker"] {
+            file_system.add_file(
+                "/test/current/services",
+                &format!("{name}.yml"),
+                &format!("service_name: {name}\nsource:\n  type: Image\n  location: {name}\n"),
+            );
+        }
+        file_system.add_file(
+            "/test/current/stacks",
+            "web.yml",
+            "name: web\ndescription: Web\nservices:\n  - api\n  - worker\n",
+        );
+        file_system.add_file(
+            "/test/current/overrides",
+            "local.yml",
+            "name: local\nstack: web\noverrides:\n  api:\n    source:\n      type: Local\
+             \n      location: ../api\n  worker:\n    source:\n      type: Local\n",
+        );
+
+        let report = validate(file_system);
+
+        let missing = find(&report, "Local source path");
+        assert!(missing.message.contains("/test/current/overrides/../api"));
+        assert_eq!(
+            missing.path.as_deref(),
+            Some(Path::new("/test/current/overrides/local.yml"))
+        );
+        assert_eq!(missing.line, Some(7));
+        let no_location = find(&report, "has no location");
+        assert_eq!(no_location.line, Some(8));
+    }
+
+    #[test]
+    fn test_reports_parse_errors_and_duplicates() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/current/services",
+            "a.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api\n",
+        );
+        file_system.add_file(
+            "/test/current/services",
+            "b.yml",
+            "service_name: api\nsource:\n  type: Image\n  location: api\n",
+        );
+        file_system.add_file(
+            "/test/current/services",
+            "broken.yml",
+            "service_name: broken\nsource:\n  type: Image\n  location: [oops\n",
+        );
+
+        let report = validate(file_system);
+
+        let duplicate = find(&report, "also defined in /test/current/services/a.yml");
+        assert_eq!(
+            duplicate.path,
+            Some(PathBuf::from("/test/current/services/b.yml"))
+        );
+
+        let parse_error = report
+            .findings
+            .iter()
+            .find(|f| f.path == Some(PathBuf::from("/test/current/services/broken.yml")))
+            .unwrap();
+        assert_eq!(parse_error.line, Some(4));
+    }
+
+    #[test]
+    fn test_reports_cycles_ports_and_missing_local_paths() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file("/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Local\n  location: ../api\ninternal_ports:\n  - 8080:80\n",
+        );
+        file_system.add_file("/test/current/services",
+            "web.yml",
+            "service_name: web\nsource:\n  type: Image\n  location: web\ninternal_ports:\n  - \"8080:3000\"\n",
+        );
+        file_system.add_file(
+            "/test/current/services",
+            "a.yml",
+            "service_name: a\nsource:\n  type: Image\n  location: a\ndependencies:\n  - b\n",
+        );
+        file_system.add_file(
+            "/test/current/services",
+            "b.yml",
+            "service_name: b\nsource:\n  type: Image\n  location: b\ndependencies:\n  - a\n",
+        );
+        file_system.add_file(
+            "/test/current/stacks",
+            "full.yml",
+            "name: full\ndescription: Full\nservices:\n  - api\n  - web\n",
+        );
+
+        let report = validate(file_system);
+
+        let collision = find(&report, "Host port 8080/tcp");
+        assert_eq!(
+            collision.path,
+            Some(PathBuf::from("/test/current/services/web.yml"))
+        );
+        assert_eq!(collision.line, Some(6));
+
+        assert!(
+            find(&report, "Dependency cycle: a -> b -> a")
+                .line
+                .is_some()
+        );
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .filter(|f| f.message.starts_with("Dependency cycle"))
+                .count(),
+            1
+        );
+
+        assert_eq!(find(&report, "Local source path").line, Some(4));
+    }
+
+    #[test]
+    fn test_clean_config_is_valid() {
+        let file_system = MockFileSystem::new();
+        file_system.add_file(
+            "/test/current/services",
+            "api.yml",
+            "service_name: api\nsource:\n  type: Local\n  location: ../api\n",
+        );
+        file_system.files.lock().unwrap().insert(
+            PathBuf::from("/test/current/services/../api/Dockerfile.dev"),
+            String::new(),
+        );
+        file_system
+            .directories
+            .lock()
+            .unwrap()
+            .insert(PathBuf::from("/test/current/services/../api"), Vec::new());
+        file_system.add_file(
+            "/test/current/stacks",
+            "full.yml",
+            "name: full\ndescription: Full\nservices:\n  - api\n",
+        );
+
+        let report = validate(file_system);
+        assert!(report.is_valid(), "{:?}", report.findings);
+        assert!(report.findings.is_empty());
+    }
+
+    #[test]
+    fn test_host_port_parsing() {
+        assert_eq!(
+            host_port("8080:80"),
+            Some(("8080/tcp".to_string(), "8080".to_string()))
+        );
+        assert_eq!(
+            host_port("127.0.0.1:53:53/udp"),
+            Some(("53/udp".to_string(), "53".to_string()))
+        );
+        assert_eq!(host_port("80"), None);
+    }
+
+    #[test]
+    fn test_contains_word() {
+        assert!(contains_word("  - api", "api"));
+        assert!(!contains_word("  - api-gateway", "api"));
+        assert!(contains_word("  - \"8080:80\"", "8080"));
+    }
+}